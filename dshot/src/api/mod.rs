@@ -1,5 +1,7 @@
 mod command;
 mod frame;
+mod telemetry;
 
 pub use command::Command;
 pub use frame::{Frame, FrameBuilder};
+pub use telemetry::{Telemetry, TelemetryError};
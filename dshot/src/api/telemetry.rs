@@ -0,0 +1,111 @@
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub enum TelemetryError {
+    Gcr,
+    Checksum,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub enum Telemetry {
+    Erpm(u32), // electrical revolutions per minute
+    Temperature(u8), // celsius
+    Voltage(u8), // 0.25V per step
+    Current(u8), // amperes
+    Debug1(u8),
+    Debug2(u8),
+    Stress(u8),
+    Status(u8),
+}
+
+impl Telemetry {
+    /// Mechanical revolutions per second, for `Erpm` frames only.
+    pub fn hz(&self, pole_pairs: u8) -> Option<f32> {
+        match self {
+            Self::Erpm(erpm) => Some(*erpm as f32 / 60.0 / pole_pairs as f32),
+            _ => None,
+        }
+    }
+
+    pub fn millivolts(&self) -> Option<u32> {
+        match self {
+            Self::Voltage(v) => Some(*v as u32 * 250),
+            _ => None,
+        }
+    }
+
+    pub fn amps(&self) -> Option<u8> {
+        match self {
+            Self::Current(a) => Some(*a),
+            _ => None,
+        }
+    }
+}
+
+const GCR_INVALID: u8 = 0xFF;
+const GCR: [u8; 32] = {
+    let mut ret = [GCR_INVALID; 32];
+    ret[0x19] = 0x0;
+    ret[0x1B] = 0x1;
+    ret[0x12] = 0x2;
+    ret[0x13] = 0x3;
+    ret[0x1D] = 0x4;
+    ret[0x15] = 0x5;
+    ret[0x16] = 0x6;
+    ret[0x17] = 0x7;
+    ret[0x1A] = 0x8;
+    ret[0x09] = 0x9;
+    ret[0x0A] = 0xA;
+    ret[0x0B] = 0xB;
+    ret[0x1E] = 0xC;
+    ret[0x0D] = 0xD;
+    ret[0x0E] = 0xE;
+    ret[0x0F] = 0xF;
+    ret
+};
+
+// period of 0x1FF << 7 is sent by ESCs for a stopped motor
+const PERIOD_STOPPED: u32 = 0x1FF << 7;
+
+impl TryFrom<u32> for Telemetry {
+    type Error = TelemetryError;
+
+    /// Decodes a 20-bit GCR word, with NRZI already removed.
+    fn try_from(gcr: u32) -> Result<Self, Self::Error> {
+        let mut value = 0u16;
+        for i in (0..4).rev() {
+            let nibble = GCR[((gcr >> (i * 5)) & 0x1F) as usize];
+            if nibble == GCR_INVALID {
+                return Err(Self::Error::Gcr);
+            }
+            value = (value << 4) | nibble as u16;
+        }
+
+        let mut crc = value ^ (value >> 8);
+        crc ^= crc >> 4;
+        if crc & 0x0F != 0x0F {
+            return Err(Self::Error::Checksum);
+        }
+
+        let data = value >> 4;
+        let (kind, payload) = ((data >> 8) as u8, data as u8);
+        // eRPM frames always have the top mantissa bit set unless exponent is 0
+        if data & 0x100 == 0 && kind != 0 {
+            let ret = match kind {
+                0x02 => Self::Temperature(payload),
+                0x04 => Self::Voltage(payload),
+                0x06 => Self::Current(payload),
+                0x08 => Self::Debug1(payload),
+                0x0A => Self::Debug2(payload),
+                0x0C => Self::Stress(payload),
+                0x0E => Self::Status(payload),
+                _ => unreachable!(),
+            };
+            return Ok(ret);
+        }
+
+        let period = ((data & 0x1FF) as u32) << (data >> 9);
+        if period == 0 || period == PERIOD_STOPPED {
+            return Ok(Self::Erpm(0));
+        }
+        Ok(Self::Erpm(60_000_000 / period))
+    }
+}
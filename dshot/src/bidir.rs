@@ -144,3 +144,23 @@ impl<'a, P: pio::Instance, const SM: usize> DshotTx for PioDshot<'a, P, SM> {
         Some(frame)
     }
 }
+
+/// Decodes a frame returned by `drain` into ESC telemetry.
+///
+/// `frame[0]` is flushed when switching the line to input, the remaining 96 bits
+/// hold 16 padding bits followed by 20 telemetry bits, 4 samples each.
+pub fn decode(frame: &[u32; 4]) -> Result<api::Telemetry, api::TelemetryError> {
+    let samples = ((frame[1] as u128) << 64) | ((frame[2] as u128) << 32) | frame[3] as u128;
+    let mut levels = 0u32; // start bit is always low
+    for i in (0..20).rev() {
+        let nibble = ((samples >> (i * 4)) & 0x0F) as u8;
+        let level = match nibble.count_ones() {
+            0 | 1 => 0,
+            2 => (nibble >> 1) & 1, // sample closest to the middle of the bit
+            _ => 1,
+        };
+        levels = (levels << 1) | level as u32;
+    }
+    let gcr = (levels ^ (levels >> 1)) & 0xFFFFF;
+    api::Telemetry::try_from(gcr)
+}
@@ -0,0 +1,131 @@
+use embassy_rp::{Peripheral, adc, dma, gpio};
+use embassy_rp::adc::AdcPin;
+
+const ADC_FULL_SCALE: f32 = 4096.0;
+
+/// Linear correction applied after scaling, `volts * gain + offset`.
+#[derive(Debug, Clone, Copy, PartialEq, bincode::Encode, bincode::Decode)]
+pub struct Calibration {
+    pub offset: f32,
+    pub gain: f32,
+}
+
+impl Default for Calibration {
+    fn default() -> Self { Self { offset: 0.0, gain: 1.0 } }
+}
+
+impl Calibration {
+    /// Two-point calibration from readings taken against a reference meter.
+    pub fn from_points(measured: (f32, f32), actual: (f32, f32)) -> Self {
+        let gain = (actual.1 - actual.0) / (measured.1 - measured.0);
+        Self { offset: actual.0 - measured.0 * gain, gain }
+    }
+
+    pub fn apply(&self, volts: f32) -> f32 { volts * self.gain + self.offset }
+
+    pub fn store(&self, buf: &mut [u8]) -> Result<usize, bincode::error::EncodeError> {
+        bincode::encode_into_slice(self, buf, bincode::config::standard())
+    }
+
+    pub fn load(buf: &[u8]) -> Result<Self, bincode::error::DecodeError> {
+        let (ret, _) = bincode::decode_from_slice(buf, bincode::config::standard())?;
+        Ok(ret)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AnalogConfig {
+    pub reference: f32, // volts at ADC full scale
+    pub r_top: f32, // divider resistors in ohms, 0.0 for a direct input
+    pub r_bottom: f32,
+    pub oversample: u8, // raw samples averaged per reading
+    pub smoothing: Option<f32>, // weight of a new reading in the IIR filter
+    pub calibration: Calibration,
+}
+
+impl Default for AnalogConfig {
+    fn default() -> Self {
+        Self {
+            reference: 3.3,
+            r_top: 0.0,
+            r_bottom: 1.0,
+            oversample: 1,
+            smoothing: None,
+            calibration: Calibration::default(),
+        }
+    }
+}
+
+impl AnalogConfig {
+    /// Volts at the divider input for an averaged raw reading.
+    pub fn scale(&self, raw: f32) -> f32 {
+        let volts = raw * self.reference / ADC_FULL_SCALE;
+        let volts = volts * (self.r_top + self.r_bottom) / self.r_bottom;
+        self.calibration.apply(volts)
+    }
+}
+
+pub struct AnalogInput<'a> {
+    channel: adc::Channel<'a>,
+    config: AnalogConfig,
+    filtered: Option<f32>,
+}
+
+impl<'a> AnalogInput<'a> {
+    pub fn new(
+        s: impl Peripheral<P = impl AdcPin> + 'a,
+        config: AnalogConfig,
+    ) -> Self {
+        let channel = adc::Channel::new_pin(s, gpio::Pull::None);
+        Self::from_channel(channel, config)
+    }
+
+    pub fn from_channel(channel: adc::Channel<'a>, config: AnalogConfig) -> Self {
+        Self { channel, config, filtered: None }
+    }
+
+    pub fn config(&self) -> &AnalogConfig { &self.config }
+
+    pub fn set_config(&mut self, config: AnalogConfig) {
+        self.config = config;
+        self.filtered = None;
+    }
+
+    pub fn calibration(&self) -> Calibration { self.config.calibration }
+    pub fn set_calibration(&mut self, calibration: Calibration) { self.config.calibration = calibration; }
+
+    /// Feeds an averaged raw reading through scaling and the optional IIR filter.
+    pub fn update(&mut self, raw: f32) -> f32 {
+        let volts = self.config.scale(raw);
+        let ret = match (self.filtered, self.config.smoothing) {
+            (Some(prev), Some(alpha)) => prev + (volts - prev) * alpha,
+            _ => volts,
+        };
+        self.filtered = Some(ret);
+        ret
+    }
+
+    pub async fn voltage(&mut self, adc: &mut adc::Adc<'_, adc::Async>) -> Result<f32, adc::Error> {
+        let samples = self.config.oversample.max(1);
+        let mut sum = 0u32;
+        for _ in 0..samples {
+            sum += adc.read(&mut self.channel).await? as u32;
+        }
+        Ok(self.update(sum as f32 / samples as f32))
+    }
+
+    /// Fills `buf` back to back through the ADC FIFO and DMA, then averages it.
+    pub async fn voltage_dma(
+        &mut self,
+        adc: &mut adc::Adc<'_, adc::Async>,
+        dma: impl Peripheral<P = impl dma::Channel>,
+        buf: &mut [u16],
+    ) -> Result<f32, adc::Error> {
+        if buf.is_empty() {
+            return self.voltage(adc).await;
+        }
+        adc.read_many(&mut self.channel, buf, 0, dma).await?;
+        let sum = buf.iter().map(|raw| *raw as u32).sum::<u32>();
+        Ok(self.update(sum as f32 / buf.len() as f32))
+    }
+}
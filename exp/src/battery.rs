@@ -0,0 +1,176 @@
+use penguin_dshot::api::Command;
+
+use crate::analog::{AnalogConfig, Calibration};
+
+/// Pack voltage divider on PIN_28, left unread unless the board has one fitted.
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
+pub struct SenseConfig {
+    pub enabled: bool,
+    pub r_top: f32, // divider resistors in ohms
+    pub r_bottom: f32,
+    pub calibration: Calibration,
+}
+
+impl Default for SenseConfig {
+    fn default() -> Self {
+        Self { enabled: false, r_top: 30_000.0, r_bottom: 10_000.0, calibration: Calibration::default() }
+    }
+}
+
+impl SenseConfig {
+    /// Measured against a 3.23V reference, `None` when disabled.
+    pub fn input_config(&self) -> Option<AnalogConfig> {
+        self.enabled.then(|| AnalogConfig {
+            reference: 3.23,
+            r_top: self.r_top,
+            r_bottom: self.r_bottom,
+            calibration: self.calibration,
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BatteryConfig {
+    pub cell_max: f32, // fully charged cell, used for cell count detection
+    pub cell_nominal: f32, // compensation reference
+    pub cell_warning: f32,
+    pub cell_critical: f32,
+    pub hysteresis: f32, // per cell, before an alarm is cleared
+    pub smoothing: f32, // weight of a new sample, 0.0..=1.0
+    pub compensation_gain: f32, // 0.0 disables sag compensation
+    pub compensation_min: f32,
+    pub compensation_max: f32,
+    pub capacity_mah: u32, // 0 disables capacity alarms
+    pub capacity_warning: f32, // fraction of capacity used
+    pub capacity_critical: f32,
+}
+
+impl Default for BatteryConfig {
+    fn default() -> Self {
+        Self {
+            cell_max: 4.3,
+            cell_nominal: 4.0,
+            cell_warning: 3.5,
+            cell_critical: 3.3,
+            hysteresis: 0.1,
+            smoothing: 0.1,
+            compensation_gain: 1.0,
+            compensation_min: 1.0,
+            compensation_max: 1.3,
+            capacity_mah: 0,
+            capacity_warning: 0.7,
+            capacity_critical: 0.85,
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, defmt::Format)]
+pub enum Alarm {
+    None,
+    Warning,
+    Critical,
+}
+
+impl From<u8> for Alarm {
+    fn from(op_0: u8) -> Self {
+        match op_0 {
+            0 => Self::None,
+            1 => Self::Warning,
+            _ => Self::Critical,
+        }
+    }
+}
+
+impl Alarm {
+    /// ESC beep and the number of control ticks between beeps, only for a stopped motor.
+    pub fn beep(&self) -> Option<(Command, u32)> {
+        match self {
+            Self::None => None,
+            Self::Warning => Some((Command::Beep { count: 1 }, 64)),
+            Self::Critical => Some((Command::Beep { count: 3 }, 12)),
+        }
+    }
+}
+
+pub struct Battery {
+    config: BatteryConfig,
+    cells: u8,
+    voltage: f32,
+    alarm: Alarm,
+    consumed_mah: f32,
+}
+
+impl Battery {
+    pub fn new(config: BatteryConfig) -> Self {
+        Self { config, cells: 0, voltage: 0.0, alarm: Alarm::None, consumed_mah: 0.0 }
+    }
+
+    pub fn config(&self) -> &BatteryConfig { &self.config }
+    pub fn set_config(&mut self, config: BatteryConfig) { self.config = config; }
+
+    /// Detected cell count, 0 until the first sample.
+    pub fn cells(&self) -> u8 { self.cells }
+    pub fn voltage(&self) -> f32 { self.voltage }
+    pub fn consumed_mah(&self) -> f32 { self.consumed_mah }
+
+    /// Worst of the voltage and capacity alarms.
+    pub fn alarm(&self) -> Alarm {
+        let capacity = self.config.capacity_mah as f32;
+        if capacity <= 0.0 {
+            return self.alarm;
+        }
+        let used = self.consumed_mah / capacity;
+        let ret = if used >= self.config.capacity_critical {
+            Alarm::Critical
+        } else if used >= self.config.capacity_warning {
+            Alarm::Warning
+        } else {
+            Alarm::None
+        };
+        if ret > self.alarm { ret } else { self.alarm }
+    }
+
+    /// Feeds consumption from a `CurrentMeter`.
+    pub fn set_consumed_mah(&mut self, mah: f32) { self.consumed_mah = mah; }
+
+    pub fn cell_voltage(&self) -> f32 {
+        if self.cells == 0 {
+            return 0.0;
+        }
+        self.voltage / self.cells as f32
+    }
+
+    pub fn update(&mut self, voltage: f32) {
+        if self.cells == 0 {
+            self.cells = libm::ceilf(voltage / self.config.cell_max) as u8;
+            self.voltage = voltage;
+        } else {
+            self.voltage += (voltage - self.voltage) * self.config.smoothing;
+        }
+        if self.cells == 0 {
+            return;
+        }
+
+        let cell = self.cell_voltage();
+        let hysteresis = self.config.hysteresis;
+        self.alarm = match self.alarm {
+            _ if cell < self.config.cell_critical => Alarm::Critical,
+            Alarm::Critical if cell < self.config.cell_critical + hysteresis => Alarm::Critical,
+            _ if cell < self.config.cell_warning => Alarm::Warning,
+            Alarm::Critical | Alarm::Warning if cell < self.config.cell_warning + hysteresis => Alarm::Warning,
+            _ => Alarm::None,
+        };
+    }
+
+    /// Throttle scale making up for sag below the nominal pack voltage.
+    pub fn compensation(&self) -> f32 {
+        if self.cells == 0 || self.voltage <= 0.0 {
+            return 1.0;
+        }
+        let nominal = self.config.cell_nominal * self.cells as f32;
+        let ret = 1.0 + (nominal / self.voltage - 1.0) * self.config.compensation_gain;
+        ret.clamp(self.config.compensation_min, self.config.compensation_max)
+    }
+}
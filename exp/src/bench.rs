@@ -0,0 +1,59 @@
+use core::f32::consts::PI;
+
+use embassy_time::Duration;
+
+#[derive(Debug, Clone)]
+pub enum Profile {
+    Step { throttle: f32, hold: Duration },
+    Ramp { from: f32, to: f32, duration: Duration },
+    Sweep { center: f32, amplitude: f32, start_hz: f32, end_hz: f32, duration: Duration },
+}
+
+impl Profile {
+    pub fn duration(&self) -> Duration {
+        match self {
+            Self::Step { hold, .. } => *hold,
+            Self::Ramp { duration, .. } | Self::Sweep { duration, .. } => *duration,
+        }
+    }
+
+    /// Throttle in `0.0..=1.0`, `elapsed` is relative to the start of this profile.
+    pub fn throttle(&self, elapsed: Duration) -> f32 {
+        let t = elapsed.as_micros() as f32 / 1e6;
+        let total = self.duration().as_micros() as f32 / 1e6;
+        let ret = match self {
+            Self::Step { throttle, .. } => *throttle,
+            Self::Ramp { from, to, .. } => from + (to - from) * (t / total).min(1.0),
+            Self::Sweep { center, amplitude, start_hz, end_hz, .. } => {
+                // linear chirp
+                let phase = 2.0 * PI * (start_hz * t + (end_hz - start_hz) * t * t / (2.0 * total));
+                center + amplitude * libm::sinf(phase)
+            }
+        };
+        ret.clamp(0.0, 1.0)
+    }
+}
+
+/// Runs profiles back to back.
+pub struct Script<'a> {
+    profiles: &'a [Profile],
+}
+
+impl<'a> Script<'a> {
+    pub fn new(profiles: &'a [Profile]) -> Self { Self { profiles } }
+
+    pub fn duration(&self) -> Duration {
+        self.profiles.iter().fold(Duration::from_ticks(0), |acc, p| acc + p.duration())
+    }
+
+    /// Throttle at `elapsed` since the script started, `None` once finished.
+    pub fn throttle(&self, mut elapsed: Duration) -> Option<f32> {
+        for profile in self.profiles {
+            if elapsed < profile.duration() {
+                return Some(profile.throttle(elapsed));
+            }
+            elapsed -= profile.duration();
+        }
+        None
+    }
+}
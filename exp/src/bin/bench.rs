@@ -0,0 +1,145 @@
+#![no_std]
+#![no_main]
+
+use penguin_dshot::DshotTx;
+use penguin_exp::uart::UartConfig;
+use penguin_exp::bench::{Profile, Script};
+use penguin_proto::bench::Row;
+
+use core::sync::atomic::{AtomicI32, Ordering};
+use heapless::String;
+
+use embassy_executor::Spawner;
+use embassy_rp::{bind_interrupts, gpio};
+use embassy_rp::{peripherals, pio};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Instant, Ticker, Timer};
+use static_cell::StaticCell;
+
+use defmt::{info, unwrap, warn};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    PIO0_IRQ_0 => pio::InterruptHandler<peripherals::PIO0>;
+});
+
+const SCRIPT: &[Profile] = &[
+    Profile::Step { throttle: 0.0, hold: Duration::from_secs(1) },
+    Profile::Step { throttle: 0.1, hold: Duration::from_secs(2) },
+    Profile::Step { throttle: 0.2, hold: Duration::from_secs(2) },
+    Profile::Step { throttle: 0.3, hold: Duration::from_secs(2) },
+    Profile::Ramp { from: 0.3, to: 0.0, duration: Duration::from_secs(3) },
+    Profile::Ramp { from: 0.0, to: 0.5, duration: Duration::from_secs(10) },
+    Profile::Sweep { center: 0.3, amplitude: 0.1, start_hz: 0.5, end_hz: 20.0, duration: Duration::from_secs(20) },
+    Profile::Ramp { from: 0.3, to: 0.0, duration: Duration::from_secs(2) },
+];
+const LOG_DIVIDER: u32 = 10; // one row every 10 control ticks
+const LOAD_CELL_SCALE: f32 = 420.0; // raw counts per gram, from a reference weight
+
+static PIO_0: StaticCell<peripherals::PIO0> = StaticCell::new();
+
+static THRUST_MG: AtomicI32 = AtomicI32::new(0);
+static ROWS: Channel<CriticalSectionRawMutex, Row, 8> = Channel::new();
+
+#[embassy_executor::task]
+async fn load_cell_task(mut load_cell: penguin_exp::hx711::Hx711<'static>) {
+    load_cell.tare(16).await;
+    load_cell.set_scale(LOAD_CELL_SCALE);
+    loop {
+        let grams = load_cell.grams().await;
+        THRUST_MG.store((grams * 1000.0) as i32, Ordering::Relaxed);
+    }
+}
+
+#[embassy_executor::task]
+async fn logger_task(mut uart: penguin_exp::uart::PioUartTx<'static, peripherals::PIO0, 0>) {
+    use embedded_io_async::Write;
+
+    let mut frame: String<96> = String::new();
+    loop {
+        let row = ROWS.receive().await;
+        frame.clear();
+        if row.write(&mut frame).is_err() {
+            warn!("row overflow");
+            continue;
+        }
+        unwrap!(uart.write_all(frame.as_bytes()).await);
+    }
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let pio_0: &'static mut _ = PIO_0.init(p.PIO0);
+    let pio::Pio {
+        mut common,
+        sm0,
+        sm1,
+        ..
+    } = pio::Pio::new(pio_0, Irqs);
+    let uart_0 = unwrap!(penguin_exp::uart::PioUartTx::new(&mut common, sm0, p.PIN_0, &UartConfig::new(115200)));
+    let mut esc_0 = penguin_dshot::bidir::PioDshot::new(&mut common, sm1, p.PIN_2);
+    unwrap!(spawner.spawn(logger_task(uart_0)));
+
+    let load_cell = penguin_exp::hx711::Hx711::new(
+        gpio::Input::new(p.PIN_4, gpio::Pull::None),
+        gpio::Output::new(p.PIN_5, gpio::Level::Low),
+        penguin_exp::hx711::Gain::A128,
+    );
+    unwrap!(spawner.spawn(load_cell_task(load_cell)));
+
+    let mut led = gpio::Output::new(p.PIN_25, gpio::Level::Low);
+    let input = gpio::Input::new(p.PIN_7, gpio::Pull::Up);
+    let mut button = penguin_exp::button::Button::new(input, Duration::from_millis(40));
+
+    esc_0.entry();
+    let mut ticker = Ticker::every(Duration::from_millis(1));
+    let arming = Instant::now();
+    while arming.elapsed() < Duration::from_secs(2) {
+        ticker.next().await;
+        esc_0.send_command(penguin_dshot::api::Command::MotorStop);
+    }
+
+    info!("press button to run {} s script", Script::new(SCRIPT).duration().as_secs());
+    while button.debounce().await != gpio::Level::High {}
+    led.set_high();
+    Timer::after_secs(1).await;
+
+    let script = Script::new(SCRIPT);
+    let mut row = Row::default();
+    let start = Instant::now();
+    let mut tick = 0u32;
+    ticker.reset();
+    loop {
+        ticker.next().await;
+        let Some(throttle) = script.throttle(start.elapsed()) else {
+            break;
+        };
+        row.throttle = (throttle * 1999.0) as u16;
+        esc_0.send_command(penguin_dshot::api::Command::Throttle(row.throttle));
+
+        use penguin_dshot::api::Telemetry;
+        match esc_0.drain().map(|frame| penguin_dshot::bidir::decode(&frame)) {
+            Some(Ok(Telemetry::Erpm(erpm))) => row.erpm = Some(erpm),
+            Some(Ok(Telemetry::Temperature(temp))) => row.temperature = Some(temp),
+            Some(Ok(telemetry @ Telemetry::Voltage(_))) => row.voltage_mv = telemetry.millivolts(),
+            Some(Ok(Telemetry::Current(current))) => row.current = Some(current),
+            _ => {}
+        }
+
+        tick = tick.wrapping_add(1);
+        if tick % LOG_DIVIDER == 0 {
+            row.time_us = start.elapsed().as_micros();
+            row.thrust = THRUST_MG.load(Ordering::Relaxed) as f32 / 1000.0;
+            if ROWS.try_send(row.clone()).is_err() {
+                warn!("logger falling behind");
+            }
+        }
+    }
+
+    esc_0.send_command(penguin_dshot::api::Command::MotorStop);
+    led.set_low();
+    info!("script done");
+}
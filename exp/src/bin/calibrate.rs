@@ -1,50 +1,107 @@
 #![no_std]
 #![no_main]
 
-use core::fmt::Write;
+use core::fmt::Write as _;
 use heapless::String;
 
 use embassy_executor::Spawner;
-use embassy_rp::{adc, bind_interrupts, pwm};
+use embassy_rp::{adc, bind_interrupts, gpio};
 use embassy_rp::{peripherals, pio};
-use embassy_time::{Duration, Ticker};
+use embassy_time::{with_timeout, Duration, Ticker, Timer};
+use embedded_io_async::Write;
 
-use defmt::info;
+use defmt::{info, unwrap, warn};
 use {defmt_rtt as _, panic_probe as _};
 
+use penguin_exp::button::Button;
+use penguin_exp::pwm_esc::{CalibrationConfig, PwmEsc};
+use penguin_exp::uart::{PioUartTx, UartConfig};
+
 bind_interrupts!(struct Irqs {
     ADC_IRQ_FIFO => adc::InterruptHandler;
     PIO0_IRQ_0 => pio::InterruptHandler<peripherals::PIO0>;
 });
 
+type Uart = PioUartTx<'static, peripherals::PIO0, 0>;
+
+async fn prompt(uart: &mut Uart, msg: &str) {
+    info!("{}", msg);
+    unwrap!(uart.write_all(msg.as_bytes()).await);
+    unwrap!(uart.write_all(b"\r\n").await);
+}
+
+async fn wait_press(button: &mut Button<'_>) {
+    while button.debounce().await != gpio::Level::High {}
+}
+
 #[embassy_executor::main]
 async fn main(_spawner: Spawner) {
     let p = embassy_rp::init(Default::default());
+    let config = CalibrationConfig::default();
+
+    // ESC must see max pulse as soon as it powers up
+    let mut esc = unwrap!(PwmEsc::new(p.PWM_SLICE3, p.PIN_22, config.frame_hz, config.max_us));
     let mut led = penguin_exp::blinker::Blinker::new(p.PIN_25, Duration::from_millis(100));
 
-    let mut pwm_0_config: pwm::Config = Default::default();
-    pwm_0_config.phase_correct = true;
-
-    let divider = 0x20u8;
-
-    let mut top = 125000000f32; // frequency of rp2040
-    top *= 20.0;
-    top /= 1000.0; // number of cycles for 20ms
-    top /= 2.0; // phase correct wave
-    top /= divider as f32;
-    top -= 1.0;
-    let top = top as u16;
-    info!("pwm cycle: {}", top);
-    pwm_0_config.divider = divider.into();
-    pwm_0_config.top = top;
-    pwm_0_config.compare_a = 0x0000;
-    let pwm_0 = pwm::Pwm::new_output_a(p.PWM_SLICE3, p.PIN_22, pwm_0_config.clone());
-
-    let mut ticker = Ticker::every(Duration::from_millis(400));
-    let mut frame = String::<128>::new();
-    while let () = ticker.next().await {
-        frame.clear();
+    let pio::Pio { mut common, sm0, .. } = pio::Pio::new(p.PIO0, Irqs);
+    let mut uart: Uart = unwrap!(PioUartTx::new(&mut common, sm0, p.PIN_0, &UartConfig::new(9600)));
+
+    let input = gpio::Input::new(p.PIN_7, gpio::Pull::Up);
+    let mut button = Button::new(input, Duration::from_millis(40));
+
+    let mut adc = adc::Adc::new(p.ADC, Irqs, adc::Config::default());
+    let mut potentiometer = penguin_exp::potentiometer::Potentiometer::new(p.PIN_29);
 
+    let mut frame: String<128> = String::new();
+    prompt(&mut uart, "ESC calibration, REMOVE PROPELLERS").await;
+    let _ = write!(frame, "range: {} us - {} us, frame {} Hz", config.min_us, config.max_us, config.frame_hz);
+    prompt(&mut uart, &frame).await;
+    prompt(&mut uart, "output at max, connect the battery and press the button after the ESC beeps").await;
+
+    if with_timeout(config.confirm_timeout, wait_press(&mut button)).await.is_err() {
+        esc.set_pulse_us(config.min_us);
+        prompt(&mut uart, "no confirmation, output at min, calibration aborted").await;
+        core::future::pending::<()>().await; // dropping the PWM would stop the signal
+    }
+
+    esc.set_pulse_us(config.min_us);
+    prompt(&mut uart, "output at min, wait for the ESC to confirm").await;
+    Timer::after(config.min_hold).await;
+
+    let full_scale = penguin_exp::potentiometer::full_scale();
+    prompt(&mut uart, "range stored, turn the potentiometer to zero").await;
+    let mut ticker = Ticker::every(Duration::from_millis(200));
+    loop {
+        ticker.next().await;
+        let pos = unwrap!(potentiometer.voltage(&mut adc).await) / full_scale;
+        if pos < config.pot_zero {
+            break;
+        }
         led.blink().await;
     }
+
+    prompt(&mut uart, "verify the range with the potentiometer, press the button to finish").await;
+    loop {
+        let pos = match potentiometer.voltage(&mut adc).await {
+            Ok(vol) => vol / full_scale,
+            Err(_) => {
+                warn!("adc read failed");
+                0.0
+            }
+        };
+        let pulse_us = config.pulse_us(pos);
+        esc.set_pulse_us(pulse_us);
+
+        frame.clear();
+        let _ = write!(frame, "pulse: {} us", pulse_us);
+        prompt(&mut uart, &frame).await;
+
+        if with_timeout(Duration::from_millis(200), wait_press(&mut button)).await.is_ok() {
+            break;
+        }
+    }
+
+    esc.set_pulse_us(config.min_us);
+    prompt(&mut uart, "done, output at min").await;
+    core::future::pending::<()>().await;
 }
@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+
+use penguin_exp::crsf::CrsfReceiver;
+use penguin_exp::uart::PioUart;
+use penguin_proto::crsf::Battery;
+
+use embassy_executor::Spawner;
+use embassy_rp::{bind_interrupts, peripherals, pio};
+use embassy_time::{Duration, Ticker};
+use static_cell::StaticCell;
+
+use defmt::{info, unwrap};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    PIO0_IRQ_0 => pio::InterruptHandler<peripherals::PIO0>;
+});
+
+static PIO_0: StaticCell<peripherals::PIO0> = StaticCell::new();
+static CRSF: CrsfReceiver = CrsfReceiver::new();
+
+#[embassy_executor::task]
+async fn crsf_task(mut uart: PioUart<'static, peripherals::PIO0, 0, 1>) {
+    CRSF.run(&mut uart).await
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let pio_0: &'static mut _ = PIO_0.init(p.PIO0);
+    let pio::Pio {
+        mut common,
+        sm0,
+        sm1,
+        ..
+    } = pio::Pio::new(pio_0, Irqs);
+    // receiver TX on PIN_1, receiver RX on PIN_0
+    let uart = unwrap!(PioUart::new(&mut common, sm0, sm1, p.PIN_0, p.PIN_1, &penguin_exp::crsf::UART));
+    unwrap!(spawner.spawn(crsf_task(uart)));
+
+    let mut ticker = Ticker::every(Duration::from_millis(500));
+    loop {
+        ticker.next().await;
+        CRSF.set_battery(Battery { voltage_dv: 168, remaining: 100, ..Default::default() });
+        match CRSF.channels() {
+            Some((channels, at)) => info!("lq: {}%, age: {} ms, ch: {}", CRSF.link_quality(), at.elapsed().as_millis(), channels.as_slice()),
+            None => info!("waiting for receiver"),
+        }
+    }
+}
@@ -2,18 +2,25 @@
 #![no_main]
 
 use penguin_dshot::DshotTx;
+use penguin_exp::analog::AnalogInput;
+use penguin_exp::battery::{Alarm, BatteryConfig};
+use penguin_exp::blinker::{Status, StatusLed, StatusSignal};
+use penguin_exp::rc::{BenchInput, RcControl};
+use penguin_exp::servo::ServoAB;
+use penguin_exp::throttle::ThrottleConfig;
+use penguin_proto::rc::{ChannelMap, FailsafeStage};
 
 use core::fmt::Write;
-use core::sync::atomic::{AtomicBool, AtomicU16, AtomicU32, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU16, AtomicU32, AtomicU8, Ordering};
 use heapless::String;
 
 use embassy_executor::Spawner;
 use embassy_rp::{adc, bind_interrupts, gpio};
 use embassy_rp::{peripherals, pio};
-use embassy_time::{Duration, Ticker, Timer};
+use embassy_time::{Duration, Instant, Ticker, Timer};
 use static_cell::StaticCell;
 
-use defmt::{info, unwrap};
+use defmt::{info, unwrap, warn};
 use embassy_rp::gpio::Pin;
 use {defmt_rtt as _, panic_probe as _};
 
@@ -22,43 +29,87 @@ bind_interrupts!(struct Irqs {
     PIO0_IRQ_0 => pio::InterruptHandler<peripherals::PIO0>;
 });
 
-static THROTTLE: AtomicU16 = AtomicU16::new(0);
+static COMPENSATION: AtomicU16 = AtomicU16::new(1000); // permille
+static ALARM: AtomicU8 = AtomicU8::new(0);
+static RC: BenchInput = BenchInput::new(ChannelMap::Aetr, 4);
+static STATUS: StatusSignal = StatusSignal::new();
 
 static PIO_0: StaticCell<peripherals::PIO0> = StaticCell::new();
 
 #[embassy_executor::task]
-async fn button_task(pin: gpio::AnyPin, mut esc_0: penguin_dshot::PioDshot<'static, peripherals::PIO0, 1>) {
+async fn button_task(pin: gpio::AnyPin, debounce: Duration) {
     let input = gpio::Input::new(pin, gpio::Pull::Up);
-    let mut button = penguin_exp::button::Button::new(input, Duration::from_millis(40));
+    let mut button = penguin_exp::button::Button::new(input, debounce);
+    RC.set_armed(false);
+    loop {
+        if button.debounce().await == gpio::Level::High {
+            info!("armed: {}", RC.toggle_armed());
+        }
+    }
+}
+
+#[embassy_executor::task]
+async fn led_task(mut led: StatusLed<'static>) {
+    led.run(&STATUS, Status::Disarmed).await
+}
+
+#[embassy_executor::task]
+async fn esc_task(mut esc_0: penguin_dshot::PioDshot<'static, peripherals::PIO0, 1>, throttle: ThrottleConfig) {
     esc_0.entry();
-    while button.debounce().await != gpio::Level::High {}
+    let mut control = RcControl::new(&RC, Default::default(), Default::default());
     let mut ticker = Ticker::every(Duration::from_millis(80));
-    let mut throttle: f32 = THROTTLE.load(Ordering::Relaxed) as f32;
+    let mut shaper = penguin_exp::throttle::ThrottleShaper::new(throttle, 12.5);
+    let mut tick = 0u32;
+    let mut demand = 0.0; // smoothed like the old fixed bench throttle
+    let mut status = Status::Disarmed;
     loop {
         ticker.next().await;
-        throttle *= 0.9;
-        throttle += THROTTLE.load(Ordering::Relaxed) as f32 * 0.1;
-        esc_0.send_command(penguin_dshot::api::Command::Throttle(throttle as u16));
+        tick = tick.wrapping_add(1);
+        let alarm = Alarm::from(ALARM.load(Ordering::Relaxed));
+        control.set_alarm(alarm == Alarm::Critical);
+        let frame = control.update();
+        let compensation = COMPENSATION.load(Ordering::Relaxed) as f32 / 1000.0;
+        // beacons replace the throttle frame, so the LED carries the alarm while armed
+        let next = match alarm {
+            _ if control.stage() != FailsafeStage::Ok => Status::Failsafe,
+            Alarm::None if frame.armed => Status::Armed,
+            Alarm::None => Status::Disarmed,
+            _ => Status::LowBattery,
+        };
+        if next != status {
+            status = next;
+            STATUS.signal(status);
+        }
+        if frame.armed {
+            demand += (frame.throttle - demand) * 0.1;
+            esc_0.send_command(shaper.command(demand * compensation));
+            continue;
+        }
+        demand = 0.0;
+        match alarm.beep() {
+            Some((beep, interval)) if tick % interval == 0 => esc_0.send_command(beep),
+            _ => esc_0.send_command(penguin_dshot::api::Command::MotorStop),
+        }
     }
 }
 
-fn to_throttle(voltage: f32) -> u16 {
-    let mut ret: f32 = 12.0; // max 12 volts
-    ret /= voltage;
-    ret *= 240.0; // base throttle
-    ret as u16
-}
-
 #[embassy_executor::main]
 async fn main(spawner: Spawner) { 
     let p = embassy_rp::init(Default::default());
 
     info!("init");
 
-    // let servo_0 = penguin_exp::servo::ServoAB::new(
-    //     p.PWM_SLICE1, p.PIN_18, p.PIN_19,
-    //     20.0, 1.2, 1.8, 0.5
-    // ); // datasheet: 0.5ms-2.5ms
+    let mut store = penguin_exp::settings::open(p.FLASH);
+    let settings = penguin_exp::settings::load(&mut store);
+
+    let [servo_a, servo_b] = settings.servo.clone();
+    let _servo_0 = match ServoAB::new(p.PWM_SLICE1, p.PIN_18, p.PIN_19, servo_a, servo_b) {
+        Ok(servo) => Some(servo), // held at center, dropping it stops the pulses
+        Err(err) => {
+            warn!("servo: {}", err);
+            None
+        }
+    };
 
     let pio_0: &'static mut _ = PIO_0.init(p.PIO0);
     let pio::Pio {
@@ -67,19 +118,44 @@ async fn main(spawner: Spawner) {
         sm1,
         ..
     } = pio::Pio::new(pio_0, Irqs);
-    let mut uart_0 = penguin_exp::uart::PioUartTx::new(&mut common, sm0, p.PIN_0, 9600);
+    let mut uart_0 = unwrap!(penguin_exp::uart::PioUartTx::new(&mut common, sm0, p.PIN_0, &settings.uart()));
     let esc_0 = penguin_dshot::PioDshot::new(&mut common, sm1, p.PIN_2);
+    unwrap!(spawner.spawn(esc_task(esc_0, settings.throttle.clone())));
     let pin_btn = p.PIN_7.degrade();
-    unwrap!(spawner.spawn(button_task(pin_btn, esc_0)));
+    unwrap!(spawner.spawn(button_task(pin_btn, settings.debounce())));
+    unwrap!(spawner.spawn(led_task(StatusLed::new(p.PWM_SLICE4, p.PIN_25))));
     
     let mut adc = adc::Adc::new(p.ADC, Irqs, adc::Config::default());
     let mut potentiometer = penguin_exp::potentiometer::Potentiometer::new(p.PIN_29);
+    potentiometer.input().set_calibration(settings.potentiometer);
+    let mut battery_input = settings.battery.input_config().map(|config| AnalogInput::new(p.PIN_28, config));
+    let mut current_input = settings.current.input_config().map(|config| AnalogInput::new(p.PIN_27, config));
+    let mut current = penguin_exp::current::CurrentMeter::new(settings.current.sensor.clone());
+    let capacity_mah = settings.current.capacity_mah;
+    let mut battery = penguin_exp::battery::Battery::new(BatteryConfig { capacity_mah, ..Default::default() });
     let mut ticker = Ticker::every(Duration::from_millis(40));
     let mut frame: String<128> = String::new();
     loop {
         ticker.next().await;
-        let vol = potentiometer.voltage(&mut adc).await.unwrap();
-        THROTTLE.store(to_throttle(vol), Ordering::Relaxed);
+        let pot = potentiometer.voltage(&mut adc).await.unwrap();
+        RC.set_throttle(pot, penguin_exp::potentiometer::full_scale());
+        if let Some(input) = battery_input.as_mut() {
+            match input.voltage(&mut adc).await {
+                Ok(vol) => battery.update(vol),
+                Err(_) => warn!("battery read failed"),
+            }
+        }
+        if let Some(input) = current_input.as_mut() {
+            match input.voltage(&mut adc).await {
+                Ok(volts) => {
+                    current.update_volts(volts, Instant::now());
+                    battery.set_consumed_mah(current.consumed_mah());
+                }
+                Err(_) => warn!("current read failed"),
+            }
+        }
+        COMPENSATION.store((battery.compensation() * 1000.0) as u16, Ordering::Relaxed);
+        ALARM.store(battery.alarm() as u8, Ordering::Relaxed);
         // frame.clear();
         // let _ = write!(frame, "vol: {}, temp: {} \r\n", vol, temp);
         // {
@@ -2,18 +2,26 @@
 #![no_main]
 
 use penguin_dshot::DshotTx;
+use penguin_exp::blinker::{Status, StatusLed, StatusSignal};
+use penguin_exp::analog::AnalogInput;
+use penguin_exp::battery::{Battery, BatteryConfig};
+use penguin_exp::current::CurrentMeter;
+use penguin_exp::sampler::{AdcReadings, AdcSampler, Sensor};
+use penguin_exp::servo::ServoAB;
+use penguin_exp::thermometer::TemperatureModel;
+use penguin_exp::uart::UartTxBuffer;
 
-use core::fmt::Write;
-use core::sync::atomic::AtomicBool;
-use heapless::String;
+use penguin_proto::telemetry::{Message, Packet, MAX_FRAME};
+
+use core::sync::atomic::{AtomicBool, AtomicU16, AtomicU32, Ordering};
 
 use embassy_executor::Spawner;
 use embassy_rp::{adc, bind_interrupts, gpio};
 use embassy_rp::{peripherals, pio};
-use embassy_time::{Duration, Ticker, Timer};
+use embassy_time::{Duration, Instant, Ticker, Timer};
 use static_cell::StaticCell;
 
-use defmt::{info, unwrap};
+use defmt::{info, unwrap, warn};
 use embassy_rp::gpio::Pin;
 use {defmt_rtt as _, panic_probe as _};
 
@@ -25,11 +33,32 @@ bind_interrupts!(struct Irqs {
 static PIO_0: StaticCell<peripherals::PIO0> = StaticCell::new();
 
 static MOTOR_ENABLED: AtomicBool = AtomicBool::new(false);
+static MOTOR_COMMAND: AtomicU16 = AtomicU16::new(0); // dshot throttle
+static ERPM: AtomicU32 = AtomicU32::new(NO_ERPM);
+const NO_ERPM: u32 = u32::MAX;
+static UART_TX: UartTxBuffer<256> = UartTxBuffer::new();
+static STATUS: StatusSignal = StatusSignal::new();
+static READINGS: AdcReadings = AdcReadings::new();
+
+#[embassy_executor::task]
+async fn adc_task(mut sampler: AdcSampler<'static, 3>, dma: peripherals::DMA_CH1) {
+    sampler.run(dma, &READINGS).await
+}
+
+#[embassy_executor::task]
+async fn led_task(mut led: StatusLed<'static>) {
+    led.run(&STATUS, Status::Disarmed).await
+}
+
+#[embassy_executor::task]
+async fn uart_task(uart: penguin_exp::uart::PioUartTx<'static, peripherals::PIO0, 0>, dma: peripherals::DMA_CH0) {
+    UART_TX.run(uart, dma).await
+}
 
 #[embassy_executor::task]
-async fn button_task(pin: gpio::AnyPin, mut esc_0: penguin_dshot::bidir::PioDshot<'static, peripherals::PIO0, 1>) {
+async fn button_task(pin: gpio::AnyPin, debounce: Duration) {
     let input = gpio::Input::new(pin, gpio::Pull::Up);
-    let mut button = penguin_exp::button::Button::new(input, Duration::from_millis(40));
+    let mut button = penguin_exp::button::Button::new(input, debounce);
     let mut state = false;
     loop {
         let level = button.debounce().await;
@@ -37,15 +66,26 @@ async fn button_task(pin: gpio::AnyPin, mut esc_0: penguin_dshot::bidir::PioDsho
             continue;
         }
         state = !state;
-        info!("sending throttle command");
-        let command = if state {
-            penguin_dshot::api::Command::Throttle(240)
-        } else {
-            penguin_dshot::api::Command::MotorStop
-        };
-        esc_0.send_command(command);
-        if let Some(frame) = esc_0.drain() {
-            info!("rsp: {:#032b}, {:#032b}, {:#032b}", frame[1], frame[2], frame[3]);
+        info!("motor enabled: {}", state);
+        MOTOR_ENABLED.store(state, Ordering::Relaxed);
+        STATUS.signal(if state { Status::Armed } else { Status::Disarmed });
+    }
+}
+
+#[embassy_executor::task]
+async fn esc_task(mut esc_0: penguin_dshot::bidir::PioDshot<'static, peripherals::PIO0, 1>) {
+    use penguin_dshot::api::{Command, Telemetry};
+    let mut ticker = Ticker::every(Duration::from_millis(10));
+    loop {
+        ticker.next().await;
+        let throttle = if MOTOR_ENABLED.load(Ordering::Relaxed) { 240 } else { 0 };
+        esc_0.send_command(if throttle > 0 { Command::Throttle(throttle) } else { Command::MotorStop });
+        MOTOR_COMMAND.store(throttle, Ordering::Relaxed);
+        match esc_0.drain().map(|frame| penguin_dshot::bidir::decode(&frame)) {
+            Some(Ok(Telemetry::Erpm(erpm))) => ERPM.store(erpm, Ordering::Relaxed),
+            Some(Ok(_)) => {}
+            Some(Err(err)) => info!("rsp: {}", err),
+            None => ERPM.store(NO_ERPM, Ordering::Relaxed),
         }
     }
 }
@@ -53,10 +93,18 @@ async fn button_task(pin: gpio::AnyPin, mut esc_0: penguin_dshot::bidir::PioDsho
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
     let p = embassy_rp::init(Default::default());
-    // let servo_0 = penguin_exp::servo::ServoAB::new(
-    //     p.PWM_SLICE1, p.PIN_18, p.PIN_19,
-    //     20.0, 1.2, 1.8, 0.5
-    // ); // datasheet: 0.5ms-2.5ms
+
+    let mut store = penguin_exp::settings::open(p.FLASH);
+    let settings = penguin_exp::settings::load(&mut store);
+
+    let [servo_a, servo_b] = settings.servo.clone();
+    let _servo_0 = match ServoAB::new(p.PWM_SLICE1, p.PIN_18, p.PIN_19, servo_a, servo_b) {
+        Ok(servo) => Some(servo), // held at center, dropping it stops the pulses
+        Err(err) => {
+            warn!("servo: {}", err);
+            None
+        }
+    };
 
     let pio_0: &'static mut _ = PIO_0.init(p.PIO0);
     let pio::Pio {
@@ -65,34 +113,75 @@ async fn main(spawner: Spawner) {
         sm1,
         ..
     } = pio::Pio::new(pio_0, Irqs);
-    let mut uart_0 = penguin_exp::uart::PioUartTx::new(&mut common, sm0, p.PIN_0, 9600);
+    let uart_0 = unwrap!(penguin_exp::uart::PioUartTx::new(&mut common, sm0, p.PIN_0, &settings.uart()));
+    unwrap!(spawner.spawn(uart_task(uart_0, p.DMA_CH0)));
+    let mut uart_0 = UART_TX.writer();
     let mut esc_0 = penguin_dshot::bidir::PioDshot::new(&mut common, sm1, p.PIN_2);
     Timer::after_secs(1).await;
     esc_0.entry();
     Timer::after_secs(1).await;
     esc_0.send_command(penguin_dshot::api::Command::MotorStop);
+    unwrap!(spawner.spawn(esc_task(esc_0)));
     let pin_btn = p.PIN_7.degrade();
-    unwrap!(spawner.spawn(button_task(pin_btn, esc_0)));
+    unwrap!(spawner.spawn(button_task(pin_btn, settings.debounce())));
+
+    unwrap!(spawner.spawn(led_task(StatusLed::new(p.PWM_SLICE4, p.PIN_25))));
 
-    let mut led = penguin_exp::blinker::Blinker::new(p.PIN_25, Duration::from_millis(100));
+    let adc = adc::Adc::new(p.ADC, Irqs, adc::Config::default());
+    let temp_sensor = AnalogInput::from_channel(adc::Channel::new_temp_sensor(p.ADC_TEMP_SENSOR), Default::default());
+    let battery_input = settings.battery.input_config()
+        .map(|config| (Sensor::BatteryVoltage, AnalogInput::new(p.PIN_28, config)));
+    let current_input = settings.current.input_config()
+        .map(|config| (Sensor::Current, AnalogInput::new(p.PIN_27, config)));
+    let sampler = AdcSampler::new(
+        adc,
+        [Some((Sensor::Temperature, temp_sensor)), battery_input, current_input]
+            .into_iter()
+            .flatten(),
+        Duration::from_millis(10),
+        16,
+    );
+    unwrap!(spawner.spawn(adc_task(sampler, p.DMA_CH1)));
 
-    let mut adc = adc::Adc::new(p.ADC, Irqs, adc::Config::default());
-    let mut potentiometer = penguin_exp::potentiometer::Potentiometer::new(p.PIN_29);
-    let mut thermometer = penguin_exp::thermometer::Thermometer::new(p.ADC_TEMP_SENSOR);
+    use embedded_io_async::Write;
+    unwrap!(uart_0.write_all(&[0]).await); // delimiter, drops boot noise on the host
 
-    let mut ticker = Ticker::every(Duration::from_millis(800));
-    let mut frame: String<128> = String::new();
+    let capacity_mah = settings.current.capacity_mah;
+    let mut battery = Battery::new(BatteryConfig { capacity_mah, ..Default::default() });
+    let mut current = CurrentMeter::new(settings.current.sensor.clone());
+    let mut thermometer = TemperatureModel::on_chip();
+    let mut ticker = Ticker::every(Duration::from_millis(100));
+    let mut frame = [0u8; MAX_FRAME];
     loop {
         ticker.next().await;
-        led.blink().await;
-
-        let vol = potentiometer.voltage(&mut adc).await.unwrap();
-        let temp = thermometer.temperature(&mut adc).await.unwrap();
-        frame.clear();
-        let _ = write!(frame, "vol: {}, temp: {} \r\n", vol, temp);
-        {
-            use embedded_io_async::Write;
-            uart_0.write(frame.as_bytes()).await.unwrap();
+
+        if let Some(vol) = READINGS.get(Sensor::BatteryVoltage) {
+            battery.update(vol);
+        }
+        if let Some(volts) = READINGS.get(Sensor::Current) {
+            current.update_volts(volts, Instant::now());
+            battery.set_consumed_mah(current.consumed_mah());
+        }
+        let temp = READINGS.get(Sensor::Temperature).map(|volts| thermometer.update(volts)).unwrap_or_default();
+        let armed = MOTOR_ENABLED.load(Ordering::Relaxed);
+        let erpm = Some(ERPM.load(Ordering::Relaxed)).filter(|erpm| *erpm != NO_ERPM);
+        let messages = [
+            Message::Motors([MOTOR_COMMAND.load(Ordering::Relaxed), 0, 0, 0]),
+            Message::Rpm([erpm, None, None, None]),
+            Message::Battery {
+                voltage_mv: (battery.voltage() * 1000.0) as u16,
+                current_ca: (current.amps() * 100.0) as i16,
+                consumed_mah: current.consumed_mah() as u16,
+                alarm: battery.alarm() as u8,
+            },
+            Message::Status { armed, temperature_cc: temp.saturating_mul_int(100).saturating_to_num() },
+        ];
+        let time_ms = Instant::now().as_millis() as u32;
+        for message in messages {
+            match Packet::new(time_ms, message).encode(&mut frame) {
+                Ok(len) => unwrap!(uart_0.write_all(&frame[..len]).await),
+                Err(err) => warn!("telemetry: {}", defmt::Debug2Format(&err)),
+            }
         }
     }
 }
@@ -0,0 +1,84 @@
+#![no_std]
+#![no_main]
+
+use penguin_dshot::api::Command;
+use penguin_dshot::DshotTx;
+use penguin_exp::mavlink::{MavlinkEndpoint, Request};
+use penguin_exp::uart::{PioUart, PioUartRx, PioUartTx, UartConfig};
+
+use embassy_executor::Spawner;
+use embassy_rp::{bind_interrupts, peripherals, pio};
+use embassy_time::{Duration, Instant, Ticker, Timer};
+use static_cell::StaticCell;
+
+use defmt::{info, unwrap};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    PIO0_IRQ_0 => pio::InterruptHandler<peripherals::PIO0>;
+});
+
+const POLE_PAIRS: u8 = 7;
+
+static PIO_0: StaticCell<peripherals::PIO0> = StaticCell::new();
+static MAVLINK: MavlinkEndpoint = MavlinkEndpoint::new(1, 1, 1); // one ESC on PIN_2
+
+#[embassy_executor::task]
+async fn tx_task(mut tx: PioUartTx<'static, peripherals::PIO0, 0>) {
+    MAVLINK.run_tx(&mut tx).await
+}
+
+#[embassy_executor::task]
+async fn rx_task(mut rx: PioUartRx<'static, peripherals::PIO0, 1>) {
+    MAVLINK.run_rx(&mut rx).await
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let pio_0: &'static mut _ = PIO_0.init(p.PIO0);
+    let pio::Pio {
+        mut common,
+        sm0,
+        sm1,
+        sm2,
+        ..
+    } = pio::Pio::new(pio_0, Irqs);
+    let uart = unwrap!(PioUart::new(&mut common, sm0, sm1, p.PIN_0, p.PIN_1, &UartConfig::new(57600)));
+    let (tx, rx) = uart.split();
+    unwrap!(spawner.spawn(tx_task(tx)));
+    unwrap!(spawner.spawn(rx_task(rx)));
+
+    let mut esc_0 = penguin_dshot::bidir::PioDshot::new(&mut common, sm2, p.PIN_2);
+    Timer::after_secs(1).await;
+    esc_0.entry();
+
+    info!("mavlink on uart 0");
+    let mut ticker = Ticker::every(Duration::from_millis(1));
+    let mut test: Option<(f32, Instant)> = None; // throttle and deadline for motor 0
+    loop {
+        ticker.next().await;
+        match MAVLINK.try_request() {
+            Some(Request::Arm(arm)) => {
+                test = None;
+                MAVLINK.update(|v| v.armed = arm);
+                info!("armed: {}", arm);
+            }
+            Some(Request::MotorTest { motor: 0, throttle, timeout }) => test = Some((throttle, Instant::now() + timeout)),
+            _ => {}
+        }
+        if test.is_some_and(|(_, deadline)| Instant::now() >= deadline) {
+            test = None;
+        }
+
+        let command = match test {
+            Some((throttle, _)) if throttle > 0.0 => Command::Throttle((throttle * 1999.0) as u16),
+            _ => Command::MotorStop,
+        };
+        esc_0.send_command(command);
+        if let Some(Ok(telemetry)) = esc_0.drain().map(|frame| penguin_dshot::bidir::decode(&frame)) {
+            MAVLINK.update_esc(0, telemetry, POLE_PAIRS);
+        }
+    }
+}
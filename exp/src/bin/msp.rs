@@ -0,0 +1,71 @@
+#![no_std]
+#![no_main]
+
+use penguin_dshot::DshotTx;
+use penguin_exp::msp::MspServer;
+use penguin_exp::uart::{PioUart, UartConfig};
+
+use embassy_executor::Spawner;
+use embassy_rp::{bind_interrupts, peripherals, pio};
+use embassy_time::{Duration, Instant, Ticker, Timer};
+use static_cell::StaticCell;
+
+use defmt::{info, unwrap};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    PIO0_IRQ_0 => pio::InterruptHandler<peripherals::PIO0>;
+});
+
+const MOTOR_TEST_TIMEOUT: Duration = Duration::from_millis(500); // stop when the tool goes quiet
+
+static PIO_0: StaticCell<peripherals::PIO0> = StaticCell::new();
+static MSP: MspServer = MspServer::new();
+
+#[embassy_executor::task]
+async fn msp_task(mut uart: PioUart<'static, peripherals::PIO0, 0, 1>) {
+    MSP.run(&mut uart).await
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let pio_0: &'static mut _ = PIO_0.init(p.PIO0);
+    let pio::Pio {
+        mut common,
+        sm0,
+        sm1,
+        sm2,
+        ..
+    } = pio::Pio::new(pio_0, Irqs);
+    let uart = unwrap!(PioUart::new(&mut common, sm0, sm1, p.PIN_0, p.PIN_1, &UartConfig::new(115200)));
+    unwrap!(spawner.spawn(msp_task(uart)));
+
+    let mut escs = [penguin_dshot::PioDshot::new(&mut common, sm2, p.PIN_2)];
+    Timer::after_secs(1).await;
+    escs.iter_mut().for_each(|esc| esc.entry());
+
+    info!("msp on uart 0");
+    let mut ticker = Ticker::every(Duration::from_millis(1));
+    let mut motors = [1000; penguin_proto::msp::MOTORS];
+    let mut last_test = Instant::now();
+    let mut last_tick = Instant::now();
+    loop {
+        ticker.next().await;
+        if let Some(values) = MSP.motor_test() {
+            motors = values;
+            last_test = Instant::now();
+        } else if last_test.elapsed() > MOTOR_TEST_TIMEOUT {
+            motors = [1000; penguin_proto::msp::MOTORS];
+        }
+        penguin_exp::msp::set_motors(&mut escs, &motors);
+
+        let cycle_time_us = last_tick.elapsed().as_micros().min(u16::MAX as u64) as u16;
+        last_tick = Instant::now();
+        MSP.update(|s| {
+            s.cycle_time_us = cycle_time_us;
+            s.motors = motors;
+        });
+    }
+}
@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+use penguin_exp::capture::{CaptureMode, PulseCapture, PulseReceiver};
+
+use embassy_executor::Spawner;
+use embassy_rp::{bind_interrupts, peripherals, pio};
+use embassy_time::{Duration, Ticker};
+use static_cell::StaticCell;
+
+use defmt::{info, unwrap};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    PIO0_IRQ_0 => pio::InterruptHandler<peripherals::PIO0>;
+});
+
+static PIO_0: StaticCell<peripherals::PIO0> = StaticCell::new();
+static PPM: PulseReceiver = PulseReceiver::new();
+
+#[embassy_executor::task]
+async fn ppm_task(mut capture: PulseCapture<'static, peripherals::PIO0, 0>) {
+    PPM.run_ppm(&mut capture, Default::default()).await
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let pio_0: &'static mut _ = PIO_0.init(p.PIO0);
+    let pio::Pio {
+        mut common,
+        sm0,
+        ..
+    } = pio::Pio::new(pio_0, Irqs);
+    let capture = PulseCapture::new(&mut common, sm0, p.PIN_3, CaptureMode::Ppm, false);
+    unwrap!(spawner.spawn(ppm_task(capture)));
+
+    let mut ticker = Ticker::every(Duration::from_millis(500));
+    loop {
+        ticker.next().await;
+        match PPM.channels() {
+            Some((channels, at)) => info!("age: {} ms, ch: {}", at.elapsed().as_millis(), channels.as_slice()),
+            None => info!("waiting for ppm"),
+        }
+    }
+}
@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+use penguin_exp::sbus::SbusReceiver;
+use penguin_exp::uart::{PioUartRx, UartConfig};
+
+use embassy_executor::Spawner;
+use embassy_rp::{bind_interrupts, peripherals, pio};
+use embassy_time::{Duration, Ticker};
+use static_cell::StaticCell;
+
+use defmt::{info, unwrap, warn};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    PIO0_IRQ_0 => pio::InterruptHandler<peripherals::PIO0>;
+});
+
+const STALL_TIMEOUT: Duration = Duration::from_millis(100);
+
+static PIO_0: StaticCell<peripherals::PIO0> = StaticCell::new();
+static SBUS: SbusReceiver = SbusReceiver::new();
+
+#[embassy_executor::task]
+async fn sbus_task(mut rx: PioUartRx<'static, peripherals::PIO0, 0>) {
+    SBUS.run(&mut rx).await
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let pio_0: &'static mut _ = PIO_0.init(p.PIO0);
+    let pio::Pio {
+        mut common,
+        sm0,
+        ..
+    } = pio::Pio::new(pio_0, Irqs);
+    let rx = unwrap!(PioUartRx::new(&mut common, sm0, p.PIN_1, &UartConfig::SBUS));
+    unwrap!(spawner.spawn(sbus_task(rx)));
+
+    let mut ticker = Ticker::every(Duration::from_millis(500));
+    loop {
+        ticker.next().await;
+        if SBUS.stalled(STALL_TIMEOUT) {
+            warn!("sbus stalled: {}", SBUS.status());
+            continue;
+        }
+        if let Some((channels, _)) = SBUS.channels() {
+            info!("ch: {}", channels.as_slice());
+        }
+    }
+}
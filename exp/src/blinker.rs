@@ -1,5 +1,8 @@
-use embassy_rp::{gpio, Peripheral};
-use embassy_time::{Duration, Timer};
+use embassy_rp::{gpio, pwm, Peripheral};
+use embassy_rp::pwm::{ChannelBPin, Slice};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Ticker, Timer};
 
 pub struct Blinker<'d> {
     pin: gpio::Output<'d>,
@@ -22,3 +25,108 @@ impl<'d> Blinker<'d> {
         Timer::after(self.duration).await;
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub enum Status {
+    Disarmed,
+    Armed,
+    Calibrating,
+    Failsafe,
+    LowBattery,
+    Error(u8), // blinked out N times, then a pause
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Step {
+    Set { level: u8, hold_ms: u32 },
+    Fade { level: u8, ms: u32 },
+}
+
+pub struct Pattern {
+    pub steps: &'static [Step],
+    pub repeat: u8,
+    pub pause_ms: u32,
+}
+
+const BLINK: &[Step] = &[Step::Set { level: 255, hold_ms: 200 }, Step::Set { level: 0, hold_ms: 200 }];
+
+impl Status {
+    pub fn pattern(&self) -> Pattern {
+        let (steps, repeat, pause_ms): (&'static [Step], u8, u32) = match self {
+            Self::Disarmed => (&[Step::Fade { level: 255, ms: 1000 }, Step::Fade { level: 0, ms: 1000 }], 1, 0),
+            Self::Armed => (&[Step::Set { level: 255, hold_ms: 1000 }], 1, 0),
+            Self::Calibrating => (&[Step::Set { level: 255, hold_ms: 100 }, Step::Set { level: 0, hold_ms: 100 }], 1, 0),
+            Self::Failsafe => (&[Step::Set { level: 255, hold_ms: 50 }, Step::Set { level: 0, hold_ms: 50 }], 2, 300),
+            Self::LowBattery => (&[Step::Set { level: 255, hold_ms: 500 }, Step::Set { level: 0, hold_ms: 500 }], 1, 0),
+            Self::Error(count) => (BLINK, *count, 1000),
+        };
+        Pattern { steps, repeat, pause_ms }
+    }
+}
+
+pub type StatusSignal = Signal<CriticalSectionRawMutex, Status>;
+
+const FRAME: Duration = Duration::from_millis(10);
+
+/// Status LED on channel B of a PWM slice, e.g. the onboard LED on `PIN_25`.
+pub struct StatusLed<'d> {
+    pwm: pwm::Pwm<'d>,
+    config: pwm::Config,
+    level: u8,
+}
+
+impl<'d> StatusLed<'d> {
+    pub fn new<T: Slice>(
+        pwm_slice: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = impl ChannelBPin<T>> + 'd,
+    ) -> Self {
+        let mut config: pwm::Config = Default::default();
+        config.top = u16::MAX;
+        config.compare_b = 0;
+        let pwm = pwm::Pwm::new_output_b(pwm_slice, pin, config.clone());
+        Self { pwm, config, level: 0 }
+    }
+
+    fn set_level(&mut self, level: u8) {
+        self.level = level;
+        self.config.compare_b = level as u16 * level as u16; // rough gamma correction
+        self.pwm.set_config(&self.config);
+    }
+
+    /// Plays the pattern of the latest status, switching within one frame of a new signal.
+    pub async fn run(&mut self, signal: &StatusSignal, initial: Status) -> ! {
+        let mut status = initial;
+        let mut ticker = Ticker::every(FRAME);
+        'pattern: loop {
+            let pattern = status.pattern();
+            let steps = (0..pattern.repeat).flat_map(|_| pattern.steps.iter().copied());
+            let pause = Step::Set { level: 0, hold_ms: pattern.pause_ms };
+            for step in steps.chain(core::iter::once(pause)) {
+                let (from, to, ms, fade) = match step {
+                    Step::Set { level, hold_ms } => (level, level, hold_ms, false),
+                    Step::Fade { level, ms } => (self.level, level, ms, true),
+                };
+                let frames = ms / FRAME.as_millis() as u32;
+                for frame in 0..frames.max(1) {
+                    let level = if fade {
+                        let t = (frame + 1) as i32 * 256 / frames.max(1) as i32;
+                        (from as i32 + (to as i32 - from as i32) * t / 256) as u8
+                    } else {
+                        to
+                    };
+                    if level != self.level {
+                        self.set_level(level);
+                    }
+                    if frames == 0 {
+                        break;
+                    }
+                    ticker.next().await;
+                    if let Some(next) = signal.try_take() {
+                        status = next;
+                        continue 'pattern;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,9 @@
 use embassy_rp::gpio;
-use embassy_time::{Duration, Timer};
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::channel::Sender;
+use embassy_time::{with_timeout, Duration, Instant};
+
+use penguin_proto::gesture::{Gesture, GestureConfig, Gestures};
 
 pub struct Button<'a> {
     input: gpio::Input<'a>,
@@ -11,11 +15,45 @@ impl<'a> Button<'a> {
         Self { input, debounce }
     }
 
+    /// Waits for an edge, then for the level to stay put for the whole debounce period.
     pub async fn debounce(&mut self) -> gpio::Level {
+        self.input.wait_for_any_edge().await;
+        self.settle().await
+    }
+
+    async fn settle(&mut self) -> gpio::Level {
+        while with_timeout(self.debounce, self.input.wait_for_any_edge()).await.is_ok() {}
+        self.input.get_level()
+    }
+
+    /// Feeds debounced edges into a gesture recognizer, the button is active low.
+    pub async fn gestures<M: RawMutex, const N: usize>(
+        &mut self,
+        config: GestureConfig,
+        sender: Sender<'_, M, Gesture, N>,
+    ) -> ! {
+        let mut gestures = Gestures::new(config);
         loop {
-            self.input.wait_for_any_edge().await;
-            Timer::after(self.debounce).await;
-            return self.input.get_level();
+            // only the raw edge races the deadline, settling is never cut short
+            let edge = match gestures.deadline() {
+                Some(deadline) => {
+                    let timeout = deadline.saturating_sub(Instant::now().as_millis());
+                    with_timeout(Duration::from_millis(timeout), self.input.wait_for_any_edge()).await.is_ok()
+                }
+                None => {
+                    self.input.wait_for_any_edge().await;
+                    true
+                }
+            };
+            let gesture = if edge {
+                let level = self.settle().await;
+                gestures.edge(level == gpio::Level::Low, Instant::now().as_millis())
+            } else {
+                gestures.timeout(Instant::now().as_millis())
+            };
+            if let Some(gesture) = gesture {
+                sender.send(gesture).await;
+            }
         }
     }
 }
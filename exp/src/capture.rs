@@ -0,0 +1,181 @@
+use core::cell::Cell;
+
+use embassy_rp::{gpio, pio};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Ticker};
+
+use fixed::traits::ToFixed;
+use fixed::types::U56F8;
+
+use penguin_proto::ppm::{PpmConfig, PpmDecoder};
+use penguin_proto::rc::{RcChannels, MAX_CHANNELS};
+
+use crate::uart::set_inverted;
+
+const COUNT_HZ: u64 = 1_000_000; // one count per microsecond, two PIO cycles per count
+const PPM_OVERHEAD_US: u32 = 3; // counts lost on edges between loops
+const POLL: Duration = Duration::from_millis(2); // 8 deep FIFO, shortest pulse is ~750 us
+
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub enum CaptureMode {
+    Ppm, // rising edge to rising edge periods
+    Pwm, // high time of each pulse
+}
+
+/// Measures pulses in PIO and queues them in the RX FIFO, polled without interrupts.
+pub struct PulseCapture<'a, P: pio::Instance, const SM: usize> {
+    sm: pio::StateMachine<'a, P, SM>,
+    mode: CaptureMode,
+}
+
+impl<'a, P: pio::Instance, const SM: usize> PulseCapture<'a, P, SM> {
+    pub fn new(
+        common: &mut pio::Common<'a, P>,
+        mut sm: pio::StateMachine<'a, P, SM>,
+        pin: impl pio::PioPin,
+        mode: CaptureMode,
+        inverted: bool, // for negative PPM
+    ) -> Self {
+        let ppm = pio_proc::pio_asm!(
+                r#"
+                ; Counts down x once per two cycles from one rising edge to the next.
+
+                    wait 0 pin 0
+                    wait 1 pin 0
+                .wrap_target
+                    mov x, ~null
+                high:
+                    jmp pin, high_next
+                    jmp low
+                high_next:
+                    jmp x-- high
+                low:
+                    jmp pin, done         ; Rising edge ends the period
+                    jmp x-- low
+                done:
+                    mov isr, ~x
+                    push noblock          ; Drop periods when the CPU falls behind
+                .wrap
+            "#
+            );
+        let pwm = pio_proc::pio_asm!(
+                r#"
+                ; Counts down x once per two cycles while the pin is high.
+
+                .wrap_target
+                    wait 0 pin 0
+                    wait 1 pin 0
+                    mov x, ~null
+                high:
+                    jmp x-- next
+                next:
+                    jmp pin, high
+                    mov isr, ~x
+                    push noblock
+                .wrap
+            "#
+            );
+        let mut pin = common.make_pio_pin(pin);
+        set_inverted(&pin, inverted);
+        pin.set_pull(if inverted { gpio::Pull::Up } else { gpio::Pull::Down });
+        sm.set_pin_dirs(pio::Direction::In, &[&pin]);
+
+        let program = match mode {
+            CaptureMode::Ppm => common.load_program(&ppm.program),
+            CaptureMode::Pwm => common.load_program(&pwm.program),
+        };
+        let mut cfg = pio::Config::default();
+        cfg.set_in_pins(&[&pin]);
+        cfg.set_jmp_pin(&pin);
+        cfg.use_program(&program, &[]);
+        cfg.fifo_join = pio::FifoJoin::RxOnly;
+        let clk = embassy_rp::clocks::clk_sys_freq();
+        cfg.clock_divider = (U56F8::from_num(clk) / (2 * COUNT_HZ)).to_fixed();
+        sm.set_config(&cfg);
+        sm.set_enable(true);
+
+        Self { sm, mode }
+    }
+
+    pub fn mode(&self) -> CaptureMode { self.mode }
+}
+
+/// A queue of measured pulses, lets captures on different state machines share a slice.
+pub trait PulseSource {
+    fn try_read_us(&mut self) -> Option<u32>;
+}
+
+impl<P: pio::Instance, const SM: usize> PulseSource for PulseCapture<'_, P, SM> {
+    fn try_read_us(&mut self) -> Option<u32> {
+        let count = self.sm.rx().try_pull()?;
+        Some(match self.mode {
+            CaptureMode::Ppm => count + PPM_OVERHEAD_US,
+            CaptureMode::Pwm => count,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct State {
+    channels: Option<(RcChannels, Instant)>,
+}
+
+/// PPM sum or parallel PWM receiver, publishes the common channel type.
+pub struct PulseReceiver {
+    state: Mutex<CriticalSectionRawMutex, Cell<State>>,
+    frames: Signal<CriticalSectionRawMutex, RcChannels>,
+}
+
+impl PulseReceiver {
+    pub const fn new() -> Self {
+        Self { state: Mutex::new(Cell::new(State { channels: None })), frames: Signal::new() }
+    }
+
+    fn publish(&self, channels: RcChannels) {
+        self.state.lock(|cell| cell.set(State { channels: Some((channels, Instant::now())) }));
+        self.frames.signal(channels);
+    }
+
+    /// Latest channels and when they arrived.
+    pub fn channels(&self) -> Option<(RcChannels, Instant)> { self.state.lock(|cell| cell.get().channels) }
+
+    /// Waits for the next frame.
+    pub async fn wait(&self) -> RcChannels { self.frames.wait().await }
+
+    pub async fn run_ppm(&self, capture: &mut impl PulseSource, config: PpmConfig) -> ! {
+        let mut decoder = PpmDecoder::new(config);
+        let mut ticker = Ticker::every(POLL);
+        loop {
+            ticker.next().await;
+            while let Some(period) = capture.try_read_us() {
+                if let Some(channels) = decoder.push(period) {
+                    self.publish(channels);
+                }
+            }
+        }
+    }
+
+    /// One input per channel, a frame is published whenever any channel updates.
+    /// Pulses outside `min_us..=max_us` are ignored.
+    pub async fn run_pwm(&self, inputs: &mut [&mut dyn PulseSource], min_us: u32, max_us: u32) -> ! {
+        let mut channels = RcChannels { count: inputs.len().min(MAX_CHANNELS) as u8, ..Default::default() };
+        let mut ticker = Ticker::every(POLL);
+        loop {
+            ticker.next().await;
+            let mut updated = false;
+            for (us, input) in channels.us.iter_mut().zip(inputs.iter_mut()) {
+                while let Some(pulse) = input.try_read_us() {
+                    if (min_us..=max_us).contains(&pulse) {
+                        *us = pulse as u16;
+                        updated = true;
+                    }
+                }
+            }
+            if updated {
+                self.publish(channels);
+            }
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant};
+use embedded_io_async::{Read, Write};
+
+use defmt::warn;
+
+use penguin_proto::crsf::{self, Attitude, Battery, LinkStatistics, Packet, Parser};
+use penguin_proto::rc::RcChannels;
+
+use crate::uart::UartConfig;
+
+pub const UART: UartConfig = UartConfig::new(crsf::BAUD);
+const DEVICE_NAME: &str = "penguin";
+const TELEMETRY_INTERVAL: Duration = Duration::from_millis(50); // receivers relay a few frames per second
+
+#[derive(Debug, Clone, Copy, Default)]
+struct State {
+    channels: Option<(RcChannels, Instant)>,
+    link: Option<LinkStatistics>,
+    battery: Option<Battery>,
+    attitude: Option<Attitude>,
+}
+
+/// CRSF receiver link, `run` owns the UART, other tasks read channels and feed telemetry.
+pub struct CrsfReceiver {
+    state: Mutex<CriticalSectionRawMutex, Cell<State>>,
+    frames: Signal<CriticalSectionRawMutex, RcChannels>,
+}
+
+impl CrsfReceiver {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(Cell::new(State { channels: None, link: None, battery: None, attitude: None })),
+            frames: Signal::new(),
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut State)) {
+        self.state.lock(|cell| {
+            let mut state = cell.get();
+            f(&mut state);
+            cell.set(state);
+        });
+    }
+
+    /// Latest channels and when they arrived.
+    pub fn channels(&self) -> Option<(RcChannels, Instant)> { self.state.lock(|cell| cell.get().channels) }
+
+    /// Waits for the next channel frame.
+    pub async fn wait(&self) -> RcChannels { self.frames.wait().await }
+
+    pub fn link(&self) -> Option<LinkStatistics> { self.state.lock(|cell| cell.get().link) }
+
+    /// Uplink quality in percent, 0 before the first statistics frame.
+    pub fn link_quality(&self) -> u8 { self.link().map_or(0, |link| link.uplink_lq) }
+
+    pub fn set_battery(&self, battery: Battery) { self.update(|s| s.battery = Some(battery)); }
+
+    pub fn set_attitude(&self, attitude: Attitude) { self.update(|s| s.attitude = Some(attitude)); }
+
+    /// Telemetry is sent right after a channel frame, when the receiver listens on a half duplex
+    /// line, at most once per `TELEMETRY_INTERVAL` and alternating between battery and attitude.
+    pub async fn run<U: Read + Write>(&self, uart: &mut U) -> ! {
+        let mut parser = Parser::new();
+        let mut rx = [0u8; 32];
+        let mut tx = [0u8; crsf::MAX_FRAME];
+        let mut turn = false;
+        let mut last_reply = Instant::MIN;
+        loop {
+            let Ok(len) = uart.read(&mut rx).await else {
+                parser.reset();
+                continue;
+            };
+            for &b in &rx[..len] {
+                let reply = match parser.push(b) {
+                    Some(Ok(Packet::RcChannels(channels))) => {
+                        self.update(|s| s.channels = Some((channels, Instant::now())));
+                        self.frames.signal(channels);
+                        if last_reply.elapsed() < TELEMETRY_INTERVAL {
+                            continue;
+                        }
+                        turn = !turn;
+                        let state = self.state.lock(|cell| cell.get());
+                        match (turn, state.battery, state.attitude) {
+                            (true, Some(battery), _) => battery.encode(&mut tx),
+                            (_, _, Some(attitude)) => attitude.encode(&mut tx),
+                            (false, Some(battery), None) => battery.encode(&mut tx),
+                            _ => continue,
+                        }
+                    }
+                    Some(Ok(Packet::LinkStatistics(link))) => {
+                        self.update(|s| s.link = Some(link));
+                        continue;
+                    }
+                    Some(Ok(Packet::DevicePing { origin, .. })) => crsf::encode_device_info(origin, DEVICE_NAME, &mut tx),
+                    Some(Ok(Packet::Other(_))) | None => continue,
+                    Some(Err(err)) => {
+                        warn!("crsf: {}", defmt::Debug2Format(&err));
+                        continue;
+                    }
+                };
+                let Ok(len) = reply else {
+                    continue;
+                };
+                last_reply = Instant::now();
+                if uart.write_all(&tx[..len]).await.is_err() {
+                    warn!("crsf: write failed");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use embassy_time::Instant;
+
+use crate::analog::AnalogConfig;
+
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
+pub struct CurrentConfig {
+    pub scale_mv_per_a: f32, // sensor sensitivity, shunt gain times resistance for amplifiers
+    pub offset_mv: f32, // output at zero current, e.g. half supply for bidirectional hall sensors
+}
+
+impl Default for CurrentConfig {
+    fn default() -> Self {
+        Self { scale_mv_per_a: 40.0, offset_mv: 0.0 }
+    }
+}
+
+/// Current sensor on PIN_27 and the pack it drains, left unread unless the board has one fitted.
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
+pub struct SenseConfig {
+    pub enabled: bool,
+    pub sensor: CurrentConfig,
+    pub capacity_mah: u32, // 0 disables the capacity alarms
+}
+
+impl Default for SenseConfig {
+    fn default() -> Self {
+        Self { enabled: false, sensor: CurrentConfig::default(), capacity_mah: 0 }
+    }
+}
+
+impl SenseConfig {
+    /// Sensor output wired straight to the pin, measured against a 3.23V reference, `None` when disabled.
+    pub fn input_config(&self) -> Option<AnalogConfig> {
+        self.enabled.then(|| AnalogConfig { reference: 3.23, oversample: 4, ..Default::default() })
+    }
+}
+
+/// Integrates current into consumed charge, from an analog sensor or ESC telemetry.
+pub struct CurrentMeter {
+    config: CurrentConfig,
+    amps: f32,
+    consumed_mah: f32,
+    last: Option<Instant>,
+}
+
+impl CurrentMeter {
+    pub fn new(config: CurrentConfig) -> Self {
+        Self { config, amps: 0.0, consumed_mah: 0.0, last: None }
+    }
+
+    pub fn config(&self) -> &CurrentConfig { &self.config }
+    pub fn set_config(&mut self, config: CurrentConfig) { self.config = config; }
+
+    pub fn amps(&self) -> f32 { self.amps }
+    pub fn consumed_mah(&self) -> f32 { self.consumed_mah }
+
+    /// Restores a count, e.g. after a reboot on the same pack.
+    pub fn set_consumed_mah(&mut self, mah: f32) { self.consumed_mah = mah; }
+
+    pub fn volts_to_amps(&self, volts: f32) -> f32 {
+        (volts * 1000.0 - self.config.offset_mv) / self.config.scale_mv_per_a
+    }
+
+    /// Feeds a filtered sensor voltage, e.g. `Sensor::Current` from `AdcReadings`.
+    pub fn update_volts(&mut self, volts: f32, now: Instant) -> f32 {
+        let amps = self.volts_to_amps(volts);
+        self.update_amps(amps, now);
+        amps
+    }
+
+    /// Feeds a total current, e.g. the sum of `Telemetry::Current` over all ESCs.
+    pub fn update_amps(&mut self, amps: f32, now: Instant) {
+        if let Some(last) = self.last {
+            let hours = (now - last).as_micros() as f32 / 3.6e9;
+            self.consumed_mah += (self.amps + amps) / 2.0 * hours * 1000.0;
+        }
+        self.amps = amps;
+        self.last = Some(now);
+    }
+}
@@ -0,0 +1,162 @@
+use core::f32::consts::PI;
+
+use penguin_dshot::api::Telemetry;
+
+/// Direct form 1 biquad, so coefficients can change without glitching the output.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+
+    weight: f32,
+}
+
+impl Biquad {
+    pub fn notch(freq: f32, sample_rate: f32, q: f32) -> Self {
+        let mut ret = Self::default();
+        ret.set_notch(freq, sample_rate, q, 1.0);
+        ret
+    }
+
+    /// Updates coefficients in place, keeping the filter state.
+    /// `weight` blends between the input (0.0) and the filtered output (1.0).
+    pub fn set_notch(&mut self, freq: f32, sample_rate: f32, q: f32, weight: f32) {
+        let omega = 2.0 * PI * freq / sample_rate;
+        let (sin, cos) = (libm::sinf(omega), libm::cosf(omega));
+        let alpha = sin / (2.0 * q);
+        let a0 = 1.0 + alpha;
+
+        self.b0 = 1.0 / a0;
+        self.b1 = -2.0 * cos / a0;
+        self.b2 = self.b0;
+        self.a1 = self.b1;
+        self.a2 = (1.0 - alpha) / a0;
+        self.weight = weight;
+    }
+
+    pub fn copy_coefficients(&mut self, other: &Self) {
+        (self.b0, self.b1, self.b2) = (other.b0, other.b1, other.b2);
+        (self.a1, self.a2) = (other.a1, other.a2);
+        self.weight = other.weight;
+    }
+
+    pub fn apply(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.b1 * self.x1 + self.b2 * self.x2 - self.a1 * self.y1 - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        x + (y - x) * self.weight
+    }
+}
+
+pub const HARMONICS: usize = 3;
+const AXES: usize = 3;
+const NYQUIST: f32 = 0.48; // highest notch, as a fraction of the sample rate
+
+#[derive(Debug, Clone)]
+pub struct RpmFilterConfig {
+    pub q: f32,
+    pub min_hz: f32,
+    pub fade_hz: f32, // notches fade in over `min_hz..min_hz + fade_hz`, 0.0 switches them on at `min_hz`
+    pub harmonics: [bool; HARMONICS],
+    pub smoothing_hz: f32, // cutoff of the low pass on motor frequency
+    pub pole_pairs: u8,
+}
+
+impl Default for RpmFilterConfig {
+    fn default() -> Self {
+        Self {
+            q: 5.0,
+            min_hz: 100.0,
+            fade_hz: 50.0,
+            harmonics: [true; HARMONICS],
+            smoothing_hz: 150.0,
+            pole_pairs: 7,
+        }
+    }
+}
+
+/// Notch filter bank tracking the fundamental and harmonics of every motor.
+pub struct RpmFilter<const MOTORS: usize> {
+    config: RpmFilterConfig,
+    sample_rate: f32,
+    smoothing: f32,
+
+    target: [f32; MOTORS],
+    freq: [f32; MOTORS],
+    notches: [[[Biquad; AXES]; HARMONICS]; MOTORS],
+}
+
+impl<const MOTORS: usize> RpmFilter<MOTORS> {
+    pub fn new(config: RpmFilterConfig, sample_rate: f32) -> Self {
+        let notch = Biquad::notch(config.min_hz.min(sample_rate * NYQUIST), sample_rate, config.q);
+        let mut ret = Self {
+            config,
+            sample_rate,
+            smoothing: 0.0,
+            target: [0.0; MOTORS],
+            freq: [0.0; MOTORS],
+            notches: [[[notch; AXES]; HARMONICS]; MOTORS],
+        };
+        ret.set_config(ret.config.clone());
+        ret
+    }
+
+    pub fn config(&self) -> &RpmFilterConfig { &self.config }
+
+    pub fn set_config(&mut self, config: RpmFilterConfig) {
+        let dt = 1.0 / self.sample_rate;
+        let rc = 1.0 / (2.0 * PI * config.smoothing_hz);
+        self.smoothing = dt / (rc + dt);
+        self.config = config;
+    }
+
+    pub fn update_telemetry(&mut self, motor: usize, telemetry: Telemetry) {
+        if let Some(hz) = telemetry.hz(self.config.pole_pairs) {
+            self.target[motor] = hz;
+        }
+    }
+
+    /// Tracks motor frequencies and recomputes coefficients, call once per sample.
+    pub fn update(&mut self) {
+        let nyquist = self.sample_rate * NYQUIST;
+        for motor in 0..MOTORS {
+            self.freq[motor] += (self.target[motor] - self.freq[motor]) * self.smoothing;
+            for harmonic in 0..HARMONICS {
+                let freq = self.freq[motor] * (harmonic + 1) as f32;
+                let weight = if !self.config.harmonics[harmonic] || freq > nyquist || freq < self.config.min_hz {
+                    0.0
+                } else if self.config.fade_hz > 0.0 {
+                    ((freq - self.config.min_hz) / self.config.fade_hz).min(1.0)
+                } else {
+                    1.0
+                };
+                // `min_hz` above nyquist leaves every notch faded out
+                let freq = freq.max(self.config.min_hz).min(nyquist);
+                let [first, rest @ ..] = &mut self.notches[motor][harmonic];
+                first.set_notch(freq, self.sample_rate, self.config.q, weight);
+                for notch in rest.iter_mut() {
+                    notch.copy_coefficients(first);
+                }
+            }
+        }
+    }
+
+    pub fn apply(&mut self, mut gyro: [f32; AXES]) -> [f32; AXES] {
+        for notches in self.notches.iter_mut().flatten() {
+            for (axis, notch) in notches.iter_mut().enumerate() {
+                gyro[axis] = notch.apply(gyro[axis]);
+            }
+        }
+        gyro
+    }
+}
@@ -0,0 +1,62 @@
+use embassy_rp::gpio;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Gain {
+    A128 = 1,
+    B32 = 2,
+    A64 = 3,
+}
+
+/// HX711 load cell amplifier, clock is bit-banged with interrupts masked since
+/// holding it high for over 60us powers the chip down.
+pub struct Hx711<'d> {
+    data: gpio::Input<'d>,
+    clock: gpio::Output<'d>,
+    gain: Gain,
+
+    offset: i32,
+    scale: f32, // raw counts per gram
+}
+
+impl<'d> Hx711<'d> {
+    pub fn new(data: gpio::Input<'d>, clock: gpio::Output<'d>, gain: Gain) -> Self {
+        Self { data, clock, gain, offset: 0, scale: 1.0 }
+    }
+
+    fn pulse(&mut self) -> bool {
+        self.clock.set_high();
+        cortex_m::asm::delay(32); // at least 0.2us at 125MHz
+        self.clock.set_low();
+        cortex_m::asm::delay(32);
+        self.data.is_high()
+    }
+
+    pub async fn read_raw(&mut self) -> i32 {
+        self.data.wait_for_low().await;
+        let raw = cortex_m::interrupt::free(|_| {
+            let mut ret = 0u32;
+            for _ in 0..24 {
+                ret = (ret << 1) | self.pulse() as u32;
+            }
+            for _ in 0..self.gain as u8 {
+                self.pulse(); // selects channel and gain of the next conversion
+            }
+            ret
+        });
+        ((raw << 8) as i32) >> 8 // sign extend 24 bits
+    }
+
+    pub async fn tare(&mut self, samples: u32) {
+        let mut sum = 0i64;
+        for _ in 0..samples {
+            sum += self.read_raw().await as i64;
+        }
+        self.offset = (sum / samples.max(1) as i64) as i32;
+    }
+
+    pub fn set_scale(&mut self, scale: f32) { self.scale = scale; }
+
+    pub async fn grams(&mut self) -> f32 {
+        (self.read_raw().await - self.offset) as f32 / self.scale
+    }
+}
@@ -4,5 +4,22 @@ pub mod button;
 pub mod blinker;
 pub mod thermometer;
 pub mod potentiometer;
+pub mod analog;
 pub mod uart;
 pub mod servo;
+pub mod filter;
+pub mod throttle;
+pub mod battery;
+pub mod current;
+pub mod hx711;
+pub mod bench;
+pub mod pwm_esc;
+pub mod motion;
+pub mod sampler;
+pub mod msp;
+pub mod mavlink;
+pub mod crsf;
+pub mod sbus;
+pub mod capture;
+pub mod rc;
+pub mod settings;
@@ -0,0 +1,272 @@
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{with_timeout, Duration, Instant};
+use embedded_io_async::{Read, Write};
+
+use defmt::warn;
+
+use penguin_dshot::api::Telemetry;
+use penguin_proto::mavlink::{self, command, mav_type, mode_flag, result, state};
+use penguin_proto::mavlink::{Attitude, BatteryStatus, CommandAck, CommandLong, EscStatus, Frame, Heartbeat, Message, Parser, SysStatus};
+
+pub const ESCS: usize = 4;
+const TICK: Duration = Duration::from_millis(100);
+const SLOW_DIVIDER: u32 = 10; // heartbeat, status and battery at 1 Hz
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Esc {
+    pub rpm: i32,
+    pub voltage: f32,
+    pub current: f32,
+}
+
+/// Vehicle state reported to the ground station.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Vehicle {
+    pub armed: bool,
+    pub failsafe: bool,
+    pub attitude: [f32; 3], // roll, pitch, yaw in rad
+    pub rates: [f32; 3], // rad/s
+    pub load: u16, // 0.1 %
+    pub voltage_mv: u16, // 0 when unknown
+    pub current_ca: i16, // -1 when unknown
+    pub consumed_mah: i32, // -1 when unknown
+    pub remaining: i8, // %, -1 when unknown
+    pub cells: u8,
+    pub escs: [Esc; ESCS],
+}
+
+/// Ground station requests, taken by the control loop.
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub enum Request {
+    Arm(bool),
+    MotorTest { motor: u8, throttle: f32, timeout: Duration }, // motor from 0, throttle 0.0..=1.0
+}
+
+pub struct MavlinkEndpoint {
+    system_id: u8,
+    component_id: u8,
+    motors: u8, // fitted, motor tests beyond these are denied
+    vehicle: Mutex<CriticalSectionRawMutex, Cell<Vehicle>>,
+    requests: Channel<CriticalSectionRawMutex, Request, 4>,
+    acks: Channel<CriticalSectionRawMutex, CommandAck, 4>,
+}
+
+impl MavlinkEndpoint {
+    /// `motors` is the number actually driven, at most `ESCS`.
+    pub const fn new(system_id: u8, component_id: u8, motors: u8) -> Self {
+        Self {
+            system_id,
+            component_id,
+            motors: if motors as usize > ESCS { ESCS as u8 } else { motors },
+            vehicle: Mutex::new(Cell::new(Vehicle {
+                armed: false,
+                failsafe: false,
+                attitude: [0.0; 3],
+                rates: [0.0; 3],
+                load: 0,
+                voltage_mv: 0,
+                current_ca: -1,
+                consumed_mah: -1,
+                remaining: -1,
+                cells: 0,
+                escs: [Esc { rpm: 0, voltage: 0.0, current: 0.0 }; ESCS],
+            })),
+            requests: Channel::new(),
+            acks: Channel::new(),
+        }
+    }
+
+    pub fn vehicle(&self) -> Vehicle { self.vehicle.lock(|cell| cell.get()) }
+
+    pub fn update(&self, f: impl FnOnce(&mut Vehicle)) {
+        self.vehicle.lock(|cell| {
+            let mut vehicle = cell.get();
+            f(&mut vehicle);
+            cell.set(vehicle);
+        });
+    }
+
+    /// Folds a decoded DShot telemetry frame into the ESC status.
+    pub fn update_esc(&self, motor: usize, telemetry: Telemetry, pole_pairs: u8) {
+        self.update(|v| {
+            let Some(esc) = v.escs.get_mut(motor) else {
+                return;
+            };
+            match telemetry {
+                Telemetry::Erpm(_) => esc.rpm = telemetry.hz(pole_pairs).map_or(0, |hz| (hz * 60.0) as i32),
+                Telemetry::Voltage(_) => esc.voltage = telemetry.millivolts().unwrap_or_default() as f32 / 1000.0,
+                Telemetry::Current(amps) => esc.current = amps as f32,
+                _ => {}
+            }
+        });
+    }
+
+    pub fn try_request(&self) -> Option<Request> { self.requests.try_receive().ok() }
+
+    fn frame(&self, seq: &mut u8, message: Message) -> Frame {
+        let ret = Frame { seq: *seq, system_id: self.system_id, component_id: self.component_id, message };
+        *seq = seq.wrapping_add(1);
+        ret
+    }
+
+    fn telemetry(&self, tick: u32) -> heapless::Vec<Message, 5> {
+        let v = self.vehicle();
+        let mut ret = heapless::Vec::new();
+        if tick % SLOW_DIVIDER == 0 {
+            let base_mode = mode_flag::CUSTOM_MODE_ENABLED | if v.armed { mode_flag::SAFETY_ARMED } else { 0 };
+            let system_status = match (v.failsafe, v.armed) {
+                (true, _) => state::CRITICAL,
+                (false, true) => state::ACTIVE,
+                (false, false) => state::STANDBY,
+            };
+            let _ = ret.push(Message::Heartbeat(Heartbeat {
+                mav_type: mav_type::QUADROTOR,
+                base_mode,
+                system_status,
+                mavlink_version: 3,
+                ..Default::default()
+            }));
+            let _ = ret.push(Message::SysStatus(SysStatus {
+                load: v.load,
+                voltage_battery: if v.voltage_mv == 0 { u16::MAX } else { v.voltage_mv },
+                current_battery: v.current_ca,
+                battery_remaining: v.remaining,
+                ..Default::default()
+            }));
+            let mut voltages = [u16::MAX; 10];
+            if v.cells > 0 && v.voltage_mv > 0 {
+                let cell = v.voltage_mv / v.cells as u16;
+                voltages.iter_mut().take(v.cells as usize).for_each(|c| *c = cell);
+            }
+            let _ = ret.push(Message::BatteryStatus(BatteryStatus {
+                current_consumed: v.consumed_mah,
+                energy_consumed: -1,
+                temperature: i16::MAX,
+                voltages,
+                current_battery: v.current_ca,
+                battery_remaining: v.remaining,
+                ..Default::default()
+            }));
+        }
+        let _ = ret.push(Message::Attitude(Attitude {
+            time_boot_ms: Instant::now().as_millis() as u32,
+            roll: v.attitude[0],
+            pitch: v.attitude[1],
+            yaw: v.attitude[2],
+            rollspeed: v.rates[0],
+            pitchspeed: v.rates[1],
+            yawspeed: v.rates[2],
+        }));
+        let _ = ret.push(Message::EscStatus(EscStatus {
+            time_usec: Instant::now().as_micros(),
+            rpm: v.escs.map(|esc| esc.rpm),
+            voltage: v.escs.map(|esc| esc.voltage),
+            current: v.escs.map(|esc| esc.current),
+            index: 0,
+        }));
+        ret
+    }
+
+    /// Streams telemetry at 10 Hz and answers commands received by `run_rx`.
+    pub async fn run_tx<W: Write>(&self, tx: &mut W) -> ! {
+        let mut buf = [0u8; mavlink::MAX_FRAME];
+        let mut seq = 0u8;
+        let mut tick = 0u32;
+        let mut next = Instant::now();
+        loop {
+            let now = Instant::now();
+            let messages = if now >= next {
+                next += TICK;
+                tick = tick.wrapping_add(1);
+                self.telemetry(tick - 1)
+            } else {
+                match with_timeout(next - now, self.acks.receive()).await {
+                    Ok(ack) => {
+                        let mut ret = heapless::Vec::new();
+                        let _ = ret.push(Message::CommandAck(ack));
+                        ret
+                    }
+                    Err(_) => continue,
+                }
+            };
+            for message in messages {
+                let Ok(len) = self.frame(&mut seq, message).encode(&mut buf) else {
+                    continue;
+                };
+                if tx.write_all(&buf[..len]).await.is_err() {
+                    warn!("mavlink: write failed");
+                }
+            }
+        }
+    }
+
+    fn command(&self, cmd: &CommandLong) -> u8 {
+        match cmd.command {
+            command::COMPONENT_ARM_DISARM => {
+                let arm = cmd.params[0] >= 0.5;
+                match self.requests.try_send(Request::Arm(arm)) {
+                    Ok(()) => result::ACCEPTED,
+                    Err(_) => result::TEMPORARILY_REJECTED,
+                }
+            }
+            command::DO_MOTOR_TEST => {
+                if self.vehicle().armed {
+                    return result::DENIED;
+                }
+                let throttle = match cmd.params[1] as u8 {
+                    0 => cmd.params[2] / 100.0, // percent
+                    1 => (cmd.params[2] - 1000.0) / 1000.0, // pwm
+                    _ => return result::UNSUPPORTED,
+                };
+                let motor = cmd.params[0] as u8;
+                if motor < 1 || motor > self.motors {
+                    return result::DENIED;
+                }
+                let request = Request::MotorTest {
+                    motor: motor - 1,
+                    throttle: throttle.clamp(0.0, 1.0),
+                    timeout: Duration::from_millis((cmd.params[3].clamp(0.0, 30.0) * 1000.0) as u64),
+                };
+                match self.requests.try_send(request) {
+                    Ok(()) => result::ACCEPTED,
+                    Err(_) => result::TEMPORARILY_REJECTED,
+                }
+            }
+            _ => result::UNSUPPORTED,
+        }
+    }
+
+    /// Parses incoming frames and handles `COMMAND_LONG` addressed to us.
+    pub async fn run_rx<R: Read>(&self, rx: &mut R) -> ! {
+        let mut parser = Parser::new();
+        let mut buf = [0u8; 32];
+        loop {
+            let Ok(len) = rx.read(&mut buf).await else {
+                parser = Parser::new();
+                continue;
+            };
+            for &b in &buf[..len] {
+                let cmd = match parser.push(b) {
+                    Some(Ok(Frame { message: Message::CommandLong(cmd), .. })) => cmd,
+                    Some(Err(mavlink::MavError::Checksum)) => {
+                        warn!("mavlink: bad checksum");
+                        continue;
+                    }
+                    _ => continue,
+                };
+                let ours = |target: u8, id: u8| target == 0 || target == id;
+                if !ours(cmd.target_system, self.system_id) || !ours(cmd.target_component, self.component_id) {
+                    continue;
+                }
+                let ack = CommandAck { command: cmd.command, result: self.command(&cmd) };
+                if self.acks.try_send(ack).is_err() {
+                    warn!("mavlink: ack dropped");
+                }
+            }
+        }
+    }
+}
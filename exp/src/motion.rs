@@ -0,0 +1,119 @@
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Ticker};
+
+use crate::servo::{Channel, ServoAB};
+
+#[derive(Debug, Clone, Copy)]
+pub struct MotionLimits {
+    pub max_velocity: f32, // deg/s
+    pub max_acceleration: f32, // deg/s^2
+}
+
+impl Default for MotionLimits {
+    fn default() -> Self {
+        Self { max_velocity: 180.0, max_acceleration: 720.0 }
+    }
+}
+
+/// Online trapezoidal profile, re-planned every step so targets can change mid-move.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Trapezoid {
+    pub position: f32,
+    pub velocity: f32,
+}
+
+impl Trapezoid {
+    /// Advances by `dt` seconds, returns true once settled on `target`.
+    pub fn step(&mut self, target: f32, dt: f32, limits: &MotionLimits) -> bool {
+        let accel = limits.max_acceleration;
+        let dv = accel * dt;
+        let error = target - self.position;
+        if libm::fabsf(error) <= libm::fabsf(self.velocity) * dt + 0.5 * dv * dt && libm::fabsf(self.velocity) <= dv {
+            self.position = target;
+            self.velocity = 0.0;
+            return true;
+        }
+
+        // fastest speed that can still stop at the target
+        let brake = libm::sqrtf(2.0 * accel * libm::fabsf(error));
+        let desired = libm::copysignf(brake.min(limits.max_velocity), error);
+        self.velocity += (desired - self.velocity).clamp(-dv, dv);
+        self.position += self.velocity * dt;
+        false
+    }
+}
+
+const CHANNELS: usize = 2;
+
+fn index(channel: Channel) -> usize {
+    match channel {
+        Channel::A => 0,
+        Channel::B => 1,
+    }
+}
+
+/// Motion layer for a `ServoAB`, `run` drives the outputs from a task while
+/// `move_to` can be awaited from any other task, one caller per channel.
+pub struct ServoMotion {
+    targets: [Signal<CriticalSectionRawMutex, f32>; CHANNELS],
+    done: [Signal<CriticalSectionRawMutex, f32>; CHANNELS],
+    limits: Mutex<CriticalSectionRawMutex, Cell<[MotionLimits; CHANNELS]>>,
+}
+
+impl ServoMotion {
+    pub const fn new(limits: MotionLimits) -> Self {
+        Self {
+            targets: [Signal::new(), Signal::new()],
+            done: [Signal::new(), Signal::new()],
+            limits: Mutex::new(Cell::new([limits; CHANNELS])),
+        }
+    }
+
+    pub fn set_limits(&self, channel: Channel, limits: MotionLimits) {
+        self.limits.lock(|cell| {
+            let mut ret = cell.get();
+            ret[index(channel)] = limits;
+            cell.set(ret);
+        });
+    }
+
+    /// Moves to an angle from center, resolves once the servo is commanded there.
+    pub async fn move_to(&self, channel: Channel, deg: f32) {
+        let idx = index(channel);
+        self.done[idx].reset();
+        self.targets[idx].signal(deg);
+        self.done[idx].wait().await;
+    }
+
+    /// Updates compare values once per PWM frame.
+    pub async fn run(&self, mut servo: ServoAB<'_>) -> ! {
+        let period = Duration::from_micros(servo.period_us() as u64);
+        let dt = servo.period_us() as f32 / 1e6;
+        let channels = [Channel::A, Channel::B];
+        let mut profiles = channels.map(|channel| Trapezoid { position: servo.angle(channel), velocity: 0.0 });
+        let mut targets = profiles.map(|profile| profile.position);
+        let mut pending = [false; CHANNELS];
+
+        let mut ticker = Ticker::every(period);
+        loop {
+            ticker.next().await;
+            let limits = self.limits.lock(|cell| cell.get());
+            for (idx, channel) in channels.into_iter().enumerate() {
+                if let Some(target) = self.targets[idx].try_take() {
+                    targets[idx] = target;
+                    pending[idx] = true;
+                }
+                let settled = profiles[idx].step(targets[idx], dt, &limits[idx]);
+                servo.set_angle(channel, profiles[idx].position);
+                if settled && pending[idx] {
+                    pending[idx] = false;
+                    self.done[idx].signal(profiles[idx].position);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embedded_io_async::{Read, Write};
+
+use defmt::warn;
+
+use penguin_dshot::api::Command;
+use penguin_dshot::DshotTx;
+use penguin_proto::msp::{self, Action, Parser, Snapshot, MOTORS};
+
+/// Maps an MSP motor value, 1000..=2000, onto a DShot command.
+pub fn motor_command(value: u16) -> Command {
+    match value {
+        ..=1000 => Command::MotorStop,
+        _ => Command::Throttle(((value.min(2000) - 1000) as u32 * 1999 / 1000) as u16),
+    }
+}
+
+/// Sends MSP motor values to a set of ESCs, extra values are ignored.
+pub fn set_motors<D: DshotTx>(escs: &mut [D], values: &[u16; MOTORS]) {
+    for (esc, value) in escs.iter_mut().zip(values) {
+        esc.send_command(motor_command(*value));
+    }
+}
+
+/// Serves MSP requests from a shared snapshot, motor tests are handed to the control loop.
+pub struct MspServer {
+    snapshot: Mutex<CriticalSectionRawMutex, Cell<Snapshot>>,
+    motors: Signal<CriticalSectionRawMutex, [u16; MOTORS]>,
+}
+
+impl MspServer {
+    pub const fn new() -> Self {
+        Self {
+            snapshot: Mutex::new(Cell::new(Snapshot {
+                cycle_time_us: 0,
+                armed: false,
+                roll: 0,
+                pitch: 0,
+                yaw: 0,
+                motors: [1000; MOTORS],
+                rc: [1500; msp::RC_CHANNELS],
+                rc_count: 0,
+                cells: 0,
+                capacity_mah: 0,
+                voltage_cv: 0,
+                current_ca: 0,
+                consumed_mah: 0,
+                rssi: 0,
+                battery_state: 3,
+            })),
+            motors: Signal::new(),
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot { self.snapshot.lock(|cell| cell.get()) }
+
+    pub fn update(&self, f: impl FnOnce(&mut Snapshot)) {
+        self.snapshot.lock(|cell| {
+            let mut snapshot = cell.get();
+            f(&mut snapshot);
+            cell.set(snapshot);
+        });
+    }
+
+    /// Motor values from the latest `MSP_SET_MOTOR`, only accepted while disarmed.
+    pub fn motor_test(&self) -> Option<[u16; MOTORS]> { self.motors.try_take() }
+
+    pub async fn run<U: Read + Write>(&self, uart: &mut U) -> ! {
+        let mut parser = Parser::new();
+        let mut rx = [0u8; 32];
+        let mut tx = [0u8; msp::MAX_PAYLOAD + 9];
+        loop {
+            let len = match uart.read(&mut rx).await {
+                Ok(len) => len,
+                Err(_) => {
+                    parser = Parser::new();
+                    continue;
+                }
+            };
+            for &b in &rx[..len] {
+                let request = match parser.push(b) {
+                    Some(Ok(request)) => request,
+                    Some(Err(err)) => {
+                        warn!("msp: {}", defmt::Debug2Format(&err));
+                        continue;
+                    }
+                    None => continue,
+                };
+                let snapshot = self.snapshot();
+                let Ok((len, action)) = msp::respond(&snapshot, &request, &mut tx) else {
+                    continue;
+                };
+                match action {
+                    Some(Action::SetMotor(values)) if !snapshot.armed => self.motors.signal(values),
+                    Some(Action::SetMotor(_)) => warn!("msp: motor test while armed"),
+                    None => {}
+                }
+                if uart.write_all(&tx[..len]).await.is_err() {
+                    warn!("msp: write failed");
+                }
+            }
+        }
+    }
+}
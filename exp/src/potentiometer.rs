@@ -1,25 +1,33 @@
-use embassy_rp::{Peripheral, adc, gpio};
+use embassy_rp::{Peripheral, adc};
 use embassy_rp::adc::AdcPin;
 
+use crate::analog::{AnalogConfig, AnalogInput};
+
+/// Input behind a 30k/10k (4:1) divider, measured against a 3.23V reference.
+pub fn config() -> AnalogConfig {
+    AnalogConfig { reference: 3.23, r_top: 30_000.0, r_bottom: 10_000.0, ..Default::default() }
+}
+
+/// Input voltage at the top of the ADC range.
+pub fn full_scale() -> f32 {
+    let config = config();
+    config.reference * (config.r_top + config.r_bottom) / config.r_bottom
+}
+
 pub struct Potentiometer<'a> {
-    channel: adc::Channel<'a>,
+    input: AnalogInput<'a>,
 }
 
 impl<'a> Potentiometer<'a> {
     pub fn new(
         s: impl Peripheral<P = impl AdcPin> + 'a
     ) -> Self {
-        let channel = adc::Channel::new_pin(s, gpio::Pull::None);
-        Self { channel }
+        Self { input: AnalogInput::new(s, config()) }
     }
 
-    pub async fn voltage(&mut self, adc: &mut adc::Adc<'_, adc::Async>) -> Result<f32, adc::Error> {
-        let raw_vol = adc.read(&mut self.channel).await?;
+    pub fn input(&mut self) -> &mut AnalogInput<'a> { &mut self.input }
 
-        let mut ret = raw_vol as f32;
-        ret *= 3.23;
-        ret *= 4.0;
-        ret /= 4096.0;
-        Ok(ret)
+    pub async fn voltage(&mut self, adc: &mut adc::Adc<'_, adc::Async>) -> Result<f32, adc::Error> {
+        self.input.voltage(adc).await
     }
 }
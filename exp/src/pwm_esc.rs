@@ -0,0 +1,67 @@
+use embassy_rp::{Peripheral, pwm};
+use embassy_rp::pwm::{ChannelAPin, Slice};
+use embassy_time::Duration;
+
+use crate::servo::{ServoError, Timing};
+
+/// Analog ESC on channel A of a PWM slice, driven in microseconds.
+pub struct PwmEsc<'d> {
+    pwm: pwm::Pwm<'d>,
+    config: pwm::Config,
+    timing: Timing,
+}
+
+impl<'d> PwmEsc<'d> {
+    pub fn new<T: Slice>(
+        pwm_slice: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = impl ChannelAPin<T>> + 'd,
+        frame_hz: u32,
+        pulse_us: u16,
+    ) -> Result<Self, ServoError> {
+        let timing = Timing::new(frame_hz)?;
+
+        let mut config: pwm::Config = Default::default();
+        config.divider = timing.divider.into();
+        config.top = timing.top;
+        config.compare_a = timing.compare(pulse_us);
+        let pwm = pwm::Pwm::new_output_a(pwm_slice, pin, config.clone());
+
+        Ok(Self { pwm, config, timing })
+    }
+
+    pub fn set_pulse_us(&mut self, pulse_us: u16) {
+        self.config.compare_a = self.timing.compare(pulse_us);
+        self.pwm.set_config(&self.config);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CalibrationConfig {
+    pub frame_hz: u32,
+    pub min_us: u16,
+    pub max_us: u16,
+    pub confirm_timeout: Duration, // ESC usually beeps within a few seconds of power-up
+    pub min_hold: Duration, // time at min pulse for the ESC to store the range
+    pub pot_zero: f32, // potentiometer must read below this before verifying
+}
+
+impl Default for CalibrationConfig {
+    fn default() -> Self {
+        Self {
+            frame_hz: 50,
+            min_us: 1000,
+            max_us: 2000,
+            confirm_timeout: Duration::from_secs(30),
+            min_hold: Duration::from_secs(4),
+            pot_zero: 0.05,
+        }
+    }
+}
+
+impl CalibrationConfig {
+    /// Pulse width for `position` in `0.0..=1.0`.
+    pub fn pulse_us(&self, position: f32) -> u16 {
+        let span = self.max_us as f32 - self.min_us as f32;
+        (self.min_us as f32 + span * position.clamp(0.0, 1.0)) as u16
+    }
+}
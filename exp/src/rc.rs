@@ -0,0 +1,101 @@
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::Instant;
+
+use penguin_proto::rc::{self, ChannelMap, Failsafe, FailsafeConfig, FailsafeStage, RcChannels, RcConfig, RcFrame};
+
+use crate::capture::PulseReceiver;
+use crate::crsf::CrsfReceiver;
+use crate::sbus::SbusReceiver;
+
+/// A source of raw channels, shared by receivers and the bench rig.
+pub trait RcInput {
+    /// Latest valid channels and when they arrived, `None` before the first frame.
+    fn channels(&self) -> Option<(RcChannels, Instant)>;
+}
+
+impl RcInput for CrsfReceiver {
+    fn channels(&self) -> Option<(RcChannels, Instant)> { CrsfReceiver::channels(self) }
+}
+
+impl RcInput for SbusReceiver {
+    fn channels(&self) -> Option<(RcChannels, Instant)> { SbusReceiver::channels(self) }
+}
+
+impl RcInput for PulseReceiver {
+    fn channels(&self) -> Option<(RcChannels, Instant)> { PulseReceiver::channels(self) }
+}
+
+/// Potentiometer throttle and an arm button, laid out as a receiver would send them.
+pub struct BenchInput {
+    map: ChannelMap,
+    arm_channel: usize,
+    channels: Mutex<CriticalSectionRawMutex, Cell<Option<(RcChannels, Instant)>>>,
+}
+
+impl BenchInput {
+    pub const fn new(map: ChannelMap, arm_channel: usize) -> Self {
+        Self { map, arm_channel, channels: Mutex::new(Cell::new(None)) }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut RcChannels)) {
+        self.channels.lock(|cell| {
+            let mut channels = cell.get().map_or(RcChannels { count: 8, ..Default::default() }, |(c, _)| c);
+            f(&mut channels);
+            cell.set(Some((channels, Instant::now())));
+        });
+    }
+
+    /// Throttle from a potentiometer voltage, `full_scale` volts at full throttle.
+    pub fn set_throttle(&self, volts: f32, full_scale: f32) {
+        let span = (rc::MAX_US - rc::MIN_US) as f32;
+        let us = rc::MIN_US + ((volts / full_scale).clamp(0.0, 1.0) * span) as u16;
+        let idx = self.map.indices()[3];
+        self.update(|c| c.us[idx] = us);
+    }
+
+    pub fn set_armed(&self, armed: bool) {
+        let idx = self.arm_channel;
+        self.update(|c| c.us[idx] = if armed { rc::MAX_US } else { rc::MIN_US });
+    }
+
+    pub fn toggle_armed(&self) -> bool {
+        let armed = self.channels().is_some_and(|(c, _)| c.us[self.arm_channel] > rc::MID_US);
+        self.set_armed(!armed);
+        !armed
+    }
+}
+
+impl RcInput for BenchInput {
+    fn channels(&self) -> Option<(RcChannels, Instant)> { self.channels.lock(|cell| cell.get()) }
+}
+
+/// Normalises any `RcInput` and runs it through the failsafe stages.
+pub struct RcControl<'a> {
+    input: &'a dyn RcInput,
+    config: RcConfig,
+    failsafe: Failsafe,
+}
+
+impl<'a> RcControl<'a> {
+    pub fn new(input: &'a dyn RcInput, config: RcConfig, failsafe: FailsafeConfig) -> Self {
+        Self { input, config, failsafe: Failsafe::new(failsafe) }
+    }
+
+    pub fn config(&self) -> &RcConfig { &self.config }
+    pub fn set_config(&mut self, config: RcConfig) { self.config = config; }
+    pub fn set_failsafe(&mut self, config: FailsafeConfig) { self.failsafe.set_config(config); }
+
+    pub fn stage(&self) -> FailsafeStage { self.failsafe.stage() }
+
+    /// Runs the failsafe action while armed, e.g. on a critical battery.
+    pub fn set_alarm(&mut self, alarm: bool) { self.failsafe.set_alarm(alarm, Instant::now().as_millis()); }
+
+    pub fn update(&mut self) -> RcFrame {
+        let latest = self.input.channels()
+            .map(|(channels, at)| (RcFrame::new(&channels, &self.config), at.as_millis()));
+        self.failsafe.update(latest, Instant::now().as_millis())
+    }
+}
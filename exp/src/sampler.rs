@@ -0,0 +1,88 @@
+use core::cell::Cell;
+
+use embassy_rp::{adc, dma, Peripheral};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Ticker};
+
+use defmt::warn;
+
+use crate::analog::AnalogInput;
+
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub enum Sensor {
+    BatteryVoltage,
+    Current,
+    Temperature,
+}
+
+const SENSORS: usize = 3;
+
+impl Sensor {
+    fn index(&self) -> usize { *self as usize }
+}
+
+/// Latest filtered voltage of every sensor, shared between tasks.
+pub struct AdcReadings {
+    values: Mutex<CriticalSectionRawMutex, Cell<[Option<f32>; SENSORS]>>,
+}
+
+impl AdcReadings {
+    pub const fn new() -> Self {
+        Self { values: Mutex::new(Cell::new([None; SENSORS])) }
+    }
+
+    pub fn set(&self, sensor: Sensor, volts: f32) {
+        self.values.lock(|cell| {
+            let mut values = cell.get();
+            values[sensor.index()] = Some(volts);
+            cell.set(values);
+        });
+    }
+
+    /// `None` until the sensor has been sampled once.
+    pub fn get(&self, sensor: Sensor) -> Option<f32> {
+        self.values.lock(|cell| cell.get()[sensor.index()])
+    }
+}
+
+/// Samples one input per tick, round robin, in bursts through the ADC FIFO and DMA.
+pub struct AdcSampler<'d, const N: usize> {
+    adc: adc::Adc<'d, adc::Async>,
+    inputs: heapless::Vec<(Sensor, AnalogInput<'d>), N>,
+    period: Duration,
+    burst: usize,
+}
+
+const MAX_BURST: usize = 32;
+
+impl<'d, const N: usize> AdcSampler<'d, N> {
+    /// Up to `N` inputs, `period` is per input so a full round takes `inputs * period`.
+    pub fn new(
+        adc: adc::Adc<'d, adc::Async>,
+        inputs: impl IntoIterator<Item = (Sensor, AnalogInput<'d>)>,
+        period: Duration,
+        burst: usize,
+    ) -> Self {
+        let inputs = inputs.into_iter().take(N).collect();
+        Self { adc, inputs, period, burst: burst.min(MAX_BURST) }
+    }
+
+    pub async fn run(&mut self, dma: impl Peripheral<P = impl dma::Channel>, readings: &AdcReadings) -> ! {
+        let mut dma = dma.into_ref();
+        let mut buf = [0u16; MAX_BURST];
+        let mut ticker = Ticker::every(self.period);
+        if self.inputs.is_empty() {
+            core::future::pending::<()>().await;
+        }
+        loop {
+            for (sensor, input) in self.inputs.iter_mut() {
+                ticker.next().await;
+                match input.voltage_dma(&mut self.adc, dma.reborrow(), &mut buf[..self.burst]).await {
+                    Ok(volts) => readings.set(*sensor, volts),
+                    Err(_) => warn!("adc read failed: {}", sensor),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{with_timeout, Duration, Instant};
+use embedded_io_async::Read;
+
+use defmt::warn;
+
+use penguin_proto::rc::RcChannels;
+use penguin_proto::sbus::{Parser, SbusFrame};
+
+/// Frames are sent every 7 or 14 ms, a quiet line this long starts a new frame.
+const FRAME_GAP: Duration = Duration::from_millis(2);
+
+#[derive(Debug, Clone, Copy, Default, defmt::Format)]
+pub struct SbusStatus {
+    pub frames: u32,
+    pub lost_frames: u32, // flagged by the receiver
+    pub errors: u32, // bad footers
+    pub failsafe: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct State {
+    channels: Option<(RcChannels, Instant)>,
+    status: SbusStatus,
+}
+
+/// SBUS receiver on an inverted 8E2 line, see `UartConfig::SBUS`.
+pub struct SbusReceiver {
+    state: Mutex<CriticalSectionRawMutex, Cell<State>>,
+    frames: Signal<CriticalSectionRawMutex, RcChannels>,
+}
+
+impl SbusReceiver {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(Cell::new(State {
+                channels: None,
+                status: SbusStatus { frames: 0, lost_frames: 0, errors: 0, failsafe: false },
+            })),
+            frames: Signal::new(),
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut State)) {
+        self.state.lock(|cell| {
+            let mut state = cell.get();
+            f(&mut state);
+            cell.set(state);
+        });
+    }
+
+    /// Latest valid channels and when they arrived, failsafe frames are not published.
+    pub fn channels(&self) -> Option<(RcChannels, Instant)> { self.state.lock(|cell| cell.get().channels) }
+
+    /// Waits for the next valid channel frame.
+    pub async fn wait(&self) -> RcChannels { self.frames.wait().await }
+
+    pub fn status(&self) -> SbusStatus { self.state.lock(|cell| cell.get().status) }
+
+    /// True when the receiver reports failsafe or no valid frame arrived within `timeout`.
+    pub fn stalled(&self, timeout: Duration) -> bool {
+        let state = self.state.lock(|cell| cell.get());
+        state.status.failsafe || state.channels.map_or(true, |(_, at)| at.elapsed() > timeout)
+    }
+
+    fn frame(&self, frame: SbusFrame) {
+        self.update(|s| {
+            s.status.frames += 1;
+            s.status.lost_frames += frame.frame_lost as u32;
+            s.status.failsafe = frame.failsafe;
+            if !frame.failsafe {
+                s.channels = Some((frame.channels, Instant::now()));
+            }
+        });
+        if !frame.failsafe {
+            self.frames.signal(frame.channels);
+        }
+    }
+
+    pub async fn run<R: Read>(&self, rx: &mut R) -> ! {
+        let mut parser = Parser::new();
+        let mut buf = [0u8; 25];
+        loop {
+            let len = match with_timeout(FRAME_GAP, rx.read(&mut buf)).await {
+                Ok(Ok(len)) => len,
+                Ok(Err(_)) | Err(_) => {
+                    parser.reset();
+                    continue;
+                }
+            };
+            for &b in &buf[..len] {
+                match parser.push(b) {
+                    Some(Ok(frame)) => self.frame(frame),
+                    Some(Err(err)) => {
+                        warn!("sbus: {}", defmt::Debug2Format(&err));
+                        self.update(|s| s.status.errors += 1);
+                    }
+                    None => {}
+                }
+            }
+        }
+    }
+}
@@ -1,13 +1,143 @@
 use embassy_rp::{Peripheral, pwm};
 use embassy_rp::pwm::{ChannelAPin, ChannelBPin, Slice};
 
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub enum Channel {
+    A,
+    B,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub enum ServoError {
+    TopOverflow, // frame too long for the PWM counter even at the largest divider
+    FrameMismatch, // both outputs of a slice share one frame rate
+    Endpoints, // `min_us` not below `max_us`, or no travel
+}
+
+/// Common servo types, pulse range and frame rate.
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub enum Profile {
+    Analog, // 1000-2000us at 50Hz
+    Digital, // 1000-2000us at 333Hz
+    NarrowBand, // 760us center at 333Hz
+    NarrowBand560, // 760us center at 560Hz, tail rotor servos
+}
+
+impl Profile {
+    pub fn frame_hz(&self) -> u32 {
+        match self {
+            Self::Analog => 50,
+            Self::Digital | Self::NarrowBand => 333,
+            Self::NarrowBand560 => 560,
+        }
+    }
+
+    pub fn range_us(&self) -> (u16, u16) {
+        match self {
+            Self::Analog | Self::Digital => (1000, 2000),
+            Self::NarrowBand | Self::NarrowBand560 => (510, 1010),
+        }
+    }
+}
+
+impl From<Profile> for ServoConfig {
+    fn from(op_0: Profile) -> Self {
+        let (min_us, max_us) = op_0.range_us();
+        Self { frame_hz: op_0.frame_hz(), min_us, max_us, ..Default::default() }
+    }
+}
+
+/// PWM divider and wrap value for a frame rate at the current `clk_sys`.
+#[derive(Debug, Clone, Copy)]
+pub struct Timing {
+    pub divider: u8,
+    pub top: u16,
+    pub ticks_per_us: f32,
+}
+
+impl Timing {
+    pub fn new(frame_hz: u32) -> Result<Self, ServoError> {
+        let clk = embassy_rp::clocks::clk_sys_freq() as u64;
+        let ticks = clk / frame_hz.max(1) as u64;
+        // smallest divider keeps the most resolution
+        let divider = ticks.div_ceil(u16::MAX as u64 + 1).max(1);
+        if divider > u8::MAX as u64 {
+            return Err(ServoError::TopOverflow);
+        }
+        let top = ticks / divider - 1;
+        let ticks_per_us = clk as f32 / divider as f32 / 1e6;
+        Ok(Self { divider: divider as u8, top: top as u16, ticks_per_us })
+    }
+
+    pub fn period_us(&self) -> u32 {
+        ((self.top as f32 + 1.0) / self.ticks_per_us) as u32
+    }
+
+    pub fn compare(&self, us: u16) -> u16 {
+        ((us as f32 * self.ticks_per_us) as u32).min(self.top as u32 + 1) as u16
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
+pub struct ServoConfig {
+    pub frame_hz: u32,
+    pub min_us: u16, // endpoint trims
+    pub max_us: u16,
+    pub trim_us: i16, // center offset
+    pub range_deg: f32, // travel between endpoints
+    pub reversed: bool,
+}
+
+impl Default for ServoConfig {
+    fn default() -> Self {
+        Self { frame_hz: 50, min_us: 1000, max_us: 2000, trim_us: 0, range_deg: 90.0, reversed: false }
+    }
+}
+
+impl ServoConfig {
+    pub fn validate(&self) -> Result<(), ServoError> {
+        if self.min_us >= self.max_us || self.range_deg <= 0.0 {
+            return Err(ServoError::Endpoints);
+        }
+        Ok(())
+    }
+
+    fn center_us(&self) -> f32 {
+        (self.min_us as f32 + self.max_us as f32) / 2.0 + self.trim_us as f32
+    }
+
+    fn us_per_deg(&self) -> f32 {
+        (self.max_us as f32 - self.min_us as f32) / self.range_deg
+    }
+
+    pub fn clamp_us(&self, us: u16) -> u16 { us.max(self.min_us).min(self.max_us) }
+
+    /// Pulse width for an angle from center, negative angles towards `min_us` unless reversed.
+    pub fn angle_to_us(&self, deg: f32) -> u16 {
+        let deg = if self.reversed { -deg } else { deg };
+        let us = self.center_us() + deg * self.us_per_deg();
+        self.clamp_us(us.clamp(0.0, u16::MAX as f32) as u16)
+    }
+
+    pub fn us_to_angle(&self, us: u16) -> f32 {
+        let deg = (us as f32 - self.center_us()) / self.us_per_deg();
+        if self.reversed { -deg } else { deg }
+    }
+}
+
+struct Output {
+    config: ServoConfig,
+    pulse_us: u16,
+}
+
+/// Two servos on the A and B outputs of a PWM slice, sharing one frame period.
 pub struct ServoAB<'d> {
     pwm: pwm::Pwm<'d>,
-    config: pwm::Config,
+    pwm_config: pwm::Config,
+    timing: Timing,
 
-    period: f32,
-    cmp_min: u16,
-    cmp_max: u16,
+    a: Output,
+    b: Output,
 }
 
 impl<'d> ServoAB<'d> {
@@ -15,53 +145,93 @@ impl<'d> ServoAB<'d> {
         pwm_slice: impl Peripheral<P = T> + 'd,
         pin_a: impl Peripheral<P = impl ChannelAPin<T>> + 'd,
         pin_b: impl Peripheral<P = impl ChannelBPin<T>> + 'd,
-        period: f32, ts_min: f32, ts_max: f32,
-        pos_init: f32,
-    ) -> Self {
-        let divider = 0x20u8;
-
-        let mut top = embassy_rp::clocks::pll_sys_freq() as f32;
-        top *= period;
-        top /= 1000.0;
-        top /= 2.0;
-        top /= divider as f32;
-        top -= 1.0;
-        assert!(top < u16::MAX as f32);
-        let top = top as u16;
-
-        let cmp_min = (ts_min * top as f32 / period) as u16;
-        let cmp_max = (ts_max * top as f32 / period) as u16;
-        let cmp_init = Self::pos_to_cmp(cmp_min, cmp_max, pos_init);
-
-        let mut config: pwm::Config = Default::default();
-        config.phase_correct = true;
-        config.divider = divider.into();
-        config.top = top;
-        config.compare_a = cmp_init;
-        config.compare_b = cmp_init;
-
-        let pwm = pwm::Pwm::new_output_ab(pwm_slice, pin_a, pin_b, config.clone());
-
-        Self {
-            pwm, config,
-            period, cmp_min, cmp_max,
+        config_a: ServoConfig,
+        config_b: ServoConfig,
+    ) -> Result<Self, ServoError> {
+        if config_a.frame_hz != config_b.frame_hz {
+            return Err(ServoError::FrameMismatch);
         }
+        config_a.validate()?;
+        config_b.validate()?;
+        let timing = Timing::new(config_a.frame_hz)?;
+
+        let a = Output { pulse_us: config_a.angle_to_us(0.0), config: config_a };
+        let b = Output { pulse_us: config_b.angle_to_us(0.0), config: config_b };
+
+        let mut pwm_config: pwm::Config = Default::default();
+        pwm_config.divider = timing.divider.into();
+        pwm_config.top = timing.top;
+        pwm_config.compare_a = timing.compare(a.pulse_us);
+        pwm_config.compare_b = timing.compare(b.pulse_us);
+        let pwm = pwm::Pwm::new_output_ab(pwm_slice, pin_a, pin_b, pwm_config.clone());
+
+        Ok(Self { pwm, pwm_config, timing, a, b })
     }
 
-    fn pos_to_cmp(cmp_min: u16, cmp_max: u16, pos: f32) -> u16 {
-        let mut ret = (cmp_max - cmp_min) as f32;
-        ret *= pos;
-        ret += cmp_min as f32;
-        ret as u16
+    fn output(&self, channel: Channel) -> &Output {
+        match channel {
+            Channel::A => &self.a,
+            Channel::B => &self.b,
+        }
     }
 
-    pub fn set_position_a(&mut self, pos: f32) {
-        self.config.compare_a = Self::pos_to_cmp(self.cmp_min, self.cmp_max, pos);
-        self.pwm.set_config(&self.config);
+    fn output_mut(&mut self, channel: Channel) -> &mut Output {
+        match channel {
+            Channel::A => &mut self.a,
+            Channel::B => &mut self.b,
+        }
     }
 
-    pub fn set_position_b(&mut self, pos: f32) {
-        self.config.compare_a = Self::pos_to_cmp(self.cmp_min, self.cmp_max, pos);
-        self.pwm.set_config(&self.config);
+    pub fn period_us(&self) -> u32 { self.timing.period_us() }
+
+    pub fn config(&self, channel: Channel) -> &ServoConfig { &self.output(channel).config }
+
+    /// Replaces trims and reversal, the commanded pulse is re-clamped to the new endpoints.
+    pub fn set_config(&mut self, channel: Channel, config: ServoConfig) -> Result<(), ServoError> {
+        if config.frame_hz != self.config(channel).frame_hz {
+            return Err(ServoError::FrameMismatch);
+        }
+        config.validate()?;
+        let pulse_us = self.output(channel).pulse_us;
+        self.output_mut(channel).config = config;
+        self.set_us(channel, pulse_us);
+        Ok(())
+    }
+
+    pub fn set_us(&mut self, channel: Channel, us: u16) {
+        let output = self.output_mut(channel);
+        output.pulse_us = output.config.clamp_us(us);
+        let pulse_us = output.pulse_us;
+        let cmp = self.timing.compare(pulse_us);
+        match channel {
+            Channel::A => self.pwm_config.compare_a = cmp,
+            Channel::B => self.pwm_config.compare_b = cmp,
+        }
+        self.pwm.set_config(&self.pwm_config);
+    }
+
+    pub fn set_angle(&mut self, channel: Channel, deg: f32) {
+        let us = self.config(channel).angle_to_us(deg);
+        self.set_us(channel, us);
     }
+
+    /// Position in `0.0..=1.0` across the configured travel.
+    pub fn set_position(&mut self, channel: Channel, pos: f32) {
+        let deg = (pos.clamp(0.0, 1.0) - 0.5) * self.config(channel).range_deg;
+        self.set_angle(channel, deg);
+    }
+
+    pub fn us(&self, channel: Channel) -> u16 { self.output(channel).pulse_us }
+
+    pub fn angle(&self, channel: Channel) -> f32 {
+        let output = self.output(channel);
+        output.config.us_to_angle(output.pulse_us)
+    }
+
+    pub fn position(&self, channel: Channel) -> f32 {
+        self.angle(channel) / self.config(channel).range_deg + 0.5
+    }
+
+    pub fn set_position_a(&mut self, pos: f32) { self.set_position(Channel::A, pos); }
+    pub fn set_position_b(&mut self, pos: f32) { self.set_position(Channel::B, pos); }
 }
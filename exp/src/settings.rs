@@ -0,0 +1,192 @@
+use embassy_rp::flash::{self, Blocking};
+use embassy_rp::peripherals::FLASH;
+use embassy_time::Duration;
+
+use defmt::{info, warn};
+
+use penguin_proto::store::{self, Loaded, Schema, Store};
+
+use crate::analog::Calibration;
+use crate::{battery, current};
+use crate::servo::ServoConfig;
+use crate::throttle::ThrottleConfig;
+use crate::uart::UartConfig;
+
+pub const FLASH_SIZE: usize = 2 * 1024 * 1024;
+/// Last two sectors, kept out of the `FLASH` region in memory.x.
+pub const SETTINGS_OFFSET: u32 = (FLASH_SIZE - 2 * store::SECTOR_SIZE) as u32;
+
+/// Tuning values kept across reboots, bump `VERSION` and extend `migrate` on layout changes.
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
+pub struct Settings {
+    pub servo: [ServoConfig; 2], // PWM_SLICE1 outputs A and B
+    pub throttle: ThrottleConfig,
+    pub debounce_ms: u32,
+    pub uart_baud: u32,
+    pub potentiometer: Calibration,
+    pub battery: battery::SenseConfig,
+    pub current: current::SenseConfig,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            servo: [ServoConfig::default(), ServoConfig::default()],
+            throttle: ThrottleConfig { max: 0.12, ..Default::default() }, // bench limit, near the old fixed 240 of 1999
+            debounce_ms: 40,
+            uart_baud: 9600,
+            potentiometer: Calibration::default(),
+            battery: battery::SenseConfig::default(),
+            current: current::SenseConfig::default(),
+        }
+    }
+}
+
+impl Schema for Settings {
+    const VERSION: u16 = 4;
+
+    fn migrate(version: u16, payload: &[u8]) -> Option<Self> {
+        match version {
+            1 => decode_exact::<SettingsV1>(payload)
+                .map(Self::from)
+                .or_else(|| decode_exact::<SettingsV1Short>(payload).map(Self::from)),
+            2 => decode_exact::<SettingsV2>(payload).map(Self::from),
+            3 => decode_exact::<SettingsV3>(payload).map(Self::from),
+            _ => None,
+        }
+    }
+}
+
+/// Version 3, before current sensing.
+#[derive(bincode::Decode)]
+struct SettingsV3 {
+    servo: [ServoConfig; 2],
+    throttle: ThrottleConfig,
+    debounce_ms: u32,
+    uart_baud: u32,
+    potentiometer: Calibration,
+    battery: battery::SenseConfig,
+}
+
+impl From<SettingsV3> for Settings {
+    fn from(op_0: SettingsV3) -> Self {
+        let SettingsV3 { servo, throttle, debounce_ms, uart_baud, potentiometer, battery } = op_0;
+        Self { servo, throttle, debounce_ms, uart_baud, potentiometer, battery, ..Default::default() }
+    }
+}
+
+/// Version 2, before battery sensing became optional.
+#[derive(bincode::Decode)]
+struct SettingsV2 {
+    servo: [ServoConfig; 2],
+    throttle: ThrottleConfig,
+    debounce_ms: u32,
+    uart_baud: u32,
+    potentiometer: Calibration,
+}
+
+impl From<SettingsV2> for Settings {
+    fn from(op_0: SettingsV2) -> Self {
+        let SettingsV2 { servo, throttle, debounce_ms, uart_baud, potentiometer } = op_0;
+        Self { servo, throttle, debounce_ms, uart_baud, potentiometer, ..Default::default() }
+    }
+}
+
+/// Version 1 as first released.
+#[derive(bincode::Decode)]
+struct SettingsV1 {
+    servo: [ServoConfig; 2],
+    throttle: ThrottleConfig,
+    debounce_ms: u32,
+    uart_baud: u32,
+    potentiometer: Calibration,
+}
+
+impl From<SettingsV1> for Settings {
+    fn from(op_0: SettingsV1) -> Self {
+        let SettingsV1 { servo, throttle, debounce_ms, uart_baud, potentiometer } = op_0;
+        Self { servo, throttle, debounce_ms, uart_baud, potentiometer, ..Default::default() }
+    }
+}
+
+/// Version 1 as briefly written without servo and potentiometer, same version number.
+#[derive(bincode::Decode)]
+struct SettingsV1Short {
+    throttle: ThrottleConfig,
+    debounce_ms: u32,
+    uart_baud: u32,
+}
+
+impl From<SettingsV1Short> for Settings {
+    fn from(op_0: SettingsV1Short) -> Self {
+        Self { throttle: op_0.throttle, debounce_ms: op_0.debounce_ms, uart_baud: op_0.uart_baud, ..Default::default() }
+    }
+}
+
+/// Decodes only if the payload is used up, which tells the two version 1 layouts apart.
+fn decode_exact<T: bincode::Decode>(payload: &[u8]) -> Option<T> {
+    match bincode::decode_from_slice(payload, bincode::config::standard()) {
+        Ok((ret, len)) if len == payload.len() => Some(ret),
+        _ => None,
+    }
+}
+
+impl Settings {
+    pub fn debounce(&self) -> Duration { Duration::from_millis(self.debounce_ms as u64) }
+
+    pub fn uart(&self) -> UartConfig { UartConfig::new(self.uart_baud) }
+}
+
+/// On-board QSPI flash, reads and writes run from RAM with interrupts held off.
+pub struct RpFlash<'d> {
+    flash: flash::Flash<'d, FLASH, Blocking, FLASH_SIZE>,
+}
+
+impl<'d> RpFlash<'d> {
+    pub fn new(flash: FLASH) -> Self {
+        Self { flash: flash::Flash::new_blocking(flash) }
+    }
+}
+
+impl store::Flash for RpFlash<'_> {
+    type Error = flash::Error;
+
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), flash::Error> {
+        self.flash.blocking_read(offset, buf)
+    }
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), flash::Error> {
+        self.flash.blocking_write(offset, data)
+    }
+
+    fn erase(&mut self, offset: u32) -> Result<(), flash::Error> {
+        self.flash.blocking_erase(offset, offset + store::SECTOR_SIZE as u32)
+    }
+}
+
+pub type SettingsStore<'d> = Store<RpFlash<'d>>;
+
+pub fn open(flash: FLASH) -> SettingsStore<'static> {
+    Store::new(RpFlash::new(flash), SETTINGS_OFFSET)
+}
+
+/// Loads the stored settings, writing defaults back when nothing valid was found.
+pub fn load(store: &mut SettingsStore<'_>) -> Settings {
+    match store.load::<Settings>() {
+        Ok((settings, Loaded::Defaults)) => {
+            info!("settings: defaults");
+            if store.save(&settings).is_err() {
+                warn!("settings: save failed");
+            }
+            settings
+        }
+        Ok((settings, loaded)) => {
+            info!("settings: {}", defmt::Debug2Format(&loaded));
+            settings
+        }
+        Err(err) => {
+            warn!("settings: {}", err);
+            Settings::default()
+        }
+    }
+}
@@ -1,7 +1,126 @@
 use embassy_rp::{Peripheral, peripherals, adc};
+use embassy_rp::adc::AdcPin;
+use fixed::types::I16F16;
 
+use crate::analog::{AnalogConfig, AnalogInput, Calibration};
+
+pub type Celsius = I16F16;
+
+const KELVIN: f32 = 273.15;
+
+/// See RP2040 datasheet, chapter 4.9.5. Temperature Sensor
+pub fn celsius(volts: f32) -> f32 { 27.0 - (volts - 0.706) / 0.001721 }
+
+#[derive(Debug, Clone, Copy)]
+pub enum NtcModel {
+    Beta { r25: f32, beta: f32 },
+    SteinhartHart { a: f32, b: f32, c: f32 },
+}
+
+impl NtcModel {
+    pub fn celsius(&self, ohms: f32) -> f32 {
+        let ln = libm::logf(ohms);
+        let inv_kelvin = match self {
+            Self::Beta { r25, beta } => 1.0 / (25.0 + KELVIN) + (ln - libm::logf(*r25)) / beta,
+            Self::SteinhartHart { a, b, c } => a + b * ln + c * ln * ln * ln,
+        };
+        1.0 / inv_kelvin - KELVIN
+    }
+}
+
+/// Thermistor in a divider with `r_series`, powered from the ADC reference.
+#[derive(Debug, Clone, Copy)]
+pub struct Ntc {
+    pub model: NtcModel,
+    pub r_series: f32,
+    pub to_ground: bool, // thermistor on the low side of the divider
+}
+
+impl Ntc {
+    pub fn ohms(&self, volts: f32, reference: f32) -> f32 {
+        let volts = volts.clamp(f32::EPSILON, reference - f32::EPSILON);
+        if self.to_ground {
+            self.r_series * volts / (reference - volts)
+        } else {
+            self.r_series * (reference - volts) / volts
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Source {
+    OnChip,
+    Ntc(Ntc),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TemperatureAlarm {
+    pub limit: Celsius,
+    pub hysteresis: Celsius,
+}
+
+/// Conversion, calibration and alarm for readings taken elsewhere, e.g. by an `AdcSampler`.
+pub struct TemperatureModel {
+    source: Source,
+    reference: f32, // ADC reference, for NTC dividers
+    calibration: Calibration, // applied in degrees
+    alarm: Option<TemperatureAlarm>,
+    over: bool,
+}
+
+impl TemperatureModel {
+    pub fn on_chip() -> Self { Self::with_source(Source::OnChip, AnalogConfig::default().reference) }
+
+    pub fn ntc(ntc: Ntc, reference: f32) -> Self { Self::with_source(Source::Ntc(ntc), reference) }
+
+    fn with_source(source: Source, reference: f32) -> Self {
+        Self { source, reference, calibration: Calibration::default(), alarm: None, over: false }
+    }
+
+    pub fn calibration(&self) -> Calibration { self.calibration }
+
+    /// Single-point calibration, shifts readings by the error at one temperature.
+    pub fn calibrate_offset(&mut self, measured: f32, actual: f32) {
+        self.calibration = Calibration { offset: actual - measured, gain: 1.0 };
+    }
+
+    /// Two-point calibration, use readings taken before calibration.
+    pub fn calibrate(&mut self, measured: (f32, f32), actual: (f32, f32)) {
+        self.calibration = Calibration::from_points(measured, actual);
+    }
+
+    pub fn set_calibration(&mut self, calibration: Calibration) { self.calibration = calibration; }
+
+    pub fn set_alarm(&mut self, alarm: Option<TemperatureAlarm>) {
+        self.alarm = alarm;
+        self.over = false;
+    }
+
+    /// Raised above the alarm limit, cleared once below it by the hysteresis.
+    pub fn over_temperature(&self) -> bool { self.over }
+
+    /// Converts a filtered voltage, e.g. from `AdcReadings`, and updates the alarm.
+    pub fn update(&mut self, volts: f32) -> Celsius {
+        let raw = match self.source {
+            Source::OnChip => celsius(volts),
+            Source::Ntc(ntc) => ntc.model.celsius(ntc.ohms(volts, self.reference)),
+        };
+        let ret = Celsius::saturating_from_num(self.calibration.apply(raw));
+
+        if let Some(alarm) = self.alarm {
+            self.over = match self.over {
+                false => ret > alarm.limit,
+                true => ret > alarm.limit - alarm.hysteresis,
+            };
+        }
+        ret
+    }
+}
+
+/// A `TemperatureModel` reading its own input.
 pub struct Thermometer<'a> {
-    channel: adc::Channel<'a>,
+    input: AnalogInput<'a>,
+    model: TemperatureModel,
 }
 
 impl<'a> Thermometer<'a> {
@@ -9,17 +128,37 @@ impl<'a> Thermometer<'a> {
         s: impl Peripheral<P = peripherals::ADC_TEMP_SENSOR> + 'a
     ) -> Self {
         let channel = adc::Channel::new_temp_sensor(s);
-        Self { channel }
+        let input = AnalogInput::from_channel(channel, AnalogConfig::default());
+        Self { input, model: TemperatureModel::on_chip() }
+    }
+
+    pub fn new_ntc(
+        s: impl Peripheral<P = impl AdcPin> + 'a,
+        ntc: Ntc,
+    ) -> Self {
+        let input = AnalogInput::new(s, AnalogConfig::default());
+        let model = TemperatureModel::ntc(ntc, input.config().reference);
+        Self { input, model }
     }
 
-    pub async fn temperature(&mut self, adc: &mut adc::Adc<'_, adc::Async>) -> Result<f32, adc::Error> {
-        let raw_temp = adc.read(&mut self.channel).await?;
+    pub fn input(&mut self) -> &mut AnalogInput<'a> { &mut self.input }
+
+    pub fn calibration(&self) -> Calibration { self.model.calibration() }
+
+    pub fn calibrate_offset(&mut self, measured: f32, actual: f32) { self.model.calibrate_offset(measured, actual); }
+
+    pub fn calibrate(&mut self, measured: (f32, f32), actual: (f32, f32)) { self.model.calibrate(measured, actual); }
+
+    pub fn set_calibration(&mut self, calibration: Calibration) { self.model.set_calibration(calibration); }
+
+    pub fn set_alarm(&mut self, alarm: Option<TemperatureAlarm>) { self.model.set_alarm(alarm); }
+
+    pub fn over_temperature(&self) -> bool { self.model.over_temperature() }
+
+    pub fn update(&mut self, volts: f32) -> Celsius { self.model.update(volts) }
 
-        // See RP2040 datasheet, chapter 4.9.5. Temperature Sensor
-        let temp = 27.0 - (raw_temp as f32 * 3.3 / 4096.0 - 0.706) / 0.001721;
-        let sign = if temp < 0.0 { -1.0 } else { 1.0 };
-        let rounded_temp_x10: i16 = ((temp * 10.0) + 0.5 * sign) as i16;
-        let ret = (rounded_temp_x10 as f32) / 10.0;
-        Ok(ret)
+    pub async fn temperature(&mut self, adc: &mut adc::Adc<'_, adc::Async>) -> Result<Celsius, adc::Error> {
+        let volts = self.input.voltage(adc).await?;
+        Ok(self.model.update(volts))
     }
 }
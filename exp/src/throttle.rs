@@ -0,0 +1,86 @@
+use core::f32::consts::PI;
+
+use penguin_dshot::api::Command;
+
+pub const CURVE_POINTS: usize = 9;
+const DSHOT_MAX: f32 = 1999.0;
+
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
+pub struct ThrottleConfig {
+    pub linearisation: f32, // 0.0 disables, boosts low outputs by up to this fraction
+    pub curve: [f32; CURVE_POINTS], // outputs for evenly spaced inputs over 0.0..=1.0
+    pub idle: f32, // fraction of full scale sent at zero throttle
+    pub max: f32, // fraction of full scale sent at full throttle
+    pub boost: f32, // gain on fast throttle changes, 0.0 disables
+    pub boost_cutoff_hz: f32,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        let mut curve = [0.0; CURVE_POINTS];
+        for (i, point) in curve.iter_mut().enumerate() {
+            *point = i as f32 / (CURVE_POINTS - 1) as f32;
+        }
+        Self {
+            linearisation: 0.0,
+            curve,
+            idle: 0.055,
+            max: 1.0,
+            boost: 0.0,
+            boost_cutoff_hz: 15.0,
+        }
+    }
+}
+
+/// Output shaping between the controller and `Command::Throttle`.
+pub struct ThrottleShaper {
+    config: ThrottleConfig,
+    sample_rate: f32,
+    smoothing: f32,
+    average: f32,
+}
+
+impl ThrottleShaper {
+    pub fn new(config: ThrottleConfig, sample_rate: f32) -> Self {
+        let mut ret = Self { config: config.clone(), sample_rate, smoothing: 0.0, average: 0.0 };
+        ret.set_config(config);
+        ret
+    }
+
+    pub fn config(&self) -> &ThrottleConfig { &self.config }
+
+    pub fn set_config(&mut self, config: ThrottleConfig) {
+        let dt = 1.0 / self.sample_rate;
+        let rc = 1.0 / (2.0 * PI * config.boost_cutoff_hz);
+        self.smoothing = dt / (rc + dt);
+        self.config = config;
+    }
+
+    fn curve(&self, input: f32) -> f32 {
+        let pos = input.clamp(0.0, 1.0) * (CURVE_POINTS - 1) as f32;
+        let idx = (pos as usize).min(CURVE_POINTS - 2);
+        let frac = pos - idx as f32;
+        let (lo, hi) = (self.config.curve[idx], self.config.curve[idx + 1]);
+        lo + (hi - lo) * frac
+    }
+
+    /// Maps a throttle demand in `0.0..=1.0` to a fraction of full scale.
+    pub fn shape(&mut self, input: f32) -> f32 {
+        let mut ret = self.curve(input);
+
+        self.average += (ret - self.average) * self.smoothing;
+        ret += (ret - self.average) * self.config.boost;
+        ret = ret.clamp(0.0, 1.0);
+
+        let inv = 1.0 - ret;
+        ret *= 1.0 + self.config.linearisation * inv * inv;
+        ret = ret.clamp(0.0, 1.0);
+
+        self.config.idle + (self.config.max - self.config.idle) * ret
+    }
+
+    pub fn command(&mut self, input: f32) -> Command {
+        let ret = self.shape(input) * DSHOT_MAX;
+        Command::Throttle(ret.clamp(0.0, DSHOT_MAX) as u16)
+    }
+}
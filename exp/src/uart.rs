@@ -1,13 +1,66 @@
+use core::cell::RefCell;
 use core::convert::Infallible;
+use core::future::poll_fn;
+use core::task::Poll;
 
-use embassy_rp::{pio, gpio};
-use embedded_io_async::{ErrorType, Write};
+use embassy_rp::{dma, pac, pio, gpio, Peripheral};
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::pipe::Pipe;
+use embassy_sync::waitqueue::MultiWakerRegistration;
+use embassy_time::{Duration, Timer};
+use embedded_io_async::{ErrorKind, ErrorType, Read, Write};
+use penguin_proto::uart::{FrameError, Framing};
+use portable_atomic::{AtomicU32, Ordering};
 
 use fixed::traits::ToFixed;
-use fixed_macro::types::U56F8;
+use fixed::types::U56F8;
+
+#[derive(Debug, Clone)]
+pub struct UartConfig {
+    pub baud: u32,
+    pub framing: Framing,
+    pub inverted: bool, // idle low, as used by SBUS
+}
+
+impl Default for UartConfig {
+    fn default() -> Self { Self::new(115200) }
+}
+
+impl UartConfig {
+    pub const SBUS: Self = Self { baud: 100_000, framing: Framing::E82, inverted: true };
+
+    /// 8n1, non-inverted.
+    pub const fn new(baud: u32) -> Self {
+        Self { baud, framing: Framing::N81, inverted: false }
+    }
+
+    /// Sets the clock divider for 8 PIO cycles per bit, returns the relative baud error.
+    fn apply(&self, cfg: &mut pio::Config<'_, impl pio::Instance>) -> Result<f32, UartError> {
+        if !self.framing.is_valid() || self.baud == 0 {
+            return Err(UartError::Config);
+        }
+        let clk = embassy_rp::clocks::clk_sys_freq();
+        cfg.clock_divider = (U56F8::from_num(clk) / (8 * self.baud as u64)).to_fixed();
+        let achieved = clk as f32 / 8.0 / cfg.clock_divider.to_num::<f32>();
+        Ok(achieved / self.baud as f32 - 1.0)
+    }
+}
+
+pub(crate) fn set_inverted(pin: &pio::Pin<'_, impl pio::Instance>, inverted: bool) {
+    use pac::io::vals::{Inover, Outover};
+    pac::IO_BANK0.gpio(pin.pin() as usize).ctrl().modify(|w| {
+        w.set_outover(if inverted { Outover::INVERT } else { Outover::NORMAL });
+        w.set_inover(if inverted { Inover::INVERT } else { Inover::NORMAL });
+    });
+}
 
 pub struct PioUartTx<'a, P: pio::Instance, const SM: usize> {
     sm_tx: pio::StateMachine<'a, P, SM>,
+    framing: Framing,
+    baud_error: f32,
+    char_time: Duration,
+    line: Option<pio::Pin<'a, P>>, // shared with the receiver, only driven while sending
 }
 
 impl<'a, P: pio::Instance, const SM: usize> PioUartTx<'a, P, SM> {
@@ -15,42 +68,96 @@ impl<'a, P: pio::Instance, const SM: usize> PioUartTx<'a, P, SM> {
         common: &mut pio::Common<'a, P>,
         mut sm_tx: pio::StateMachine<'a, P, SM>,
         tx_pin: impl pio::PioPin,
-        baud: u64,
-    ) -> Self {
+        config: &UartConfig,
+    ) -> Result<Self, UartError> {
+        let tx_pin = common.make_pio_pin(tx_pin);
+        set_inverted(&tx_pin, config.inverted);
+        sm_tx.set_pins(gpio::Level::High, &[&tx_pin]);
+        sm_tx.set_pin_dirs(pio::Direction::Out, &[&tx_pin]);
+        Self::from_pin(common, sm_tx, &tx_pin, config)
+    }
+
+    fn from_pin(
+        common: &mut pio::Common<'a, P>,
+        mut sm_tx: pio::StateMachine<'a, P, SM>,
+        tx_pin: &pio::Pin<'a, P>,
+        config: &UartConfig,
+    ) -> Result<Self, UartError> {
         let prg = pio_proc::pio_asm!(
                 r#"
                 .side_set 1 opt
 
-                ; An 8n1 UART transmit program.
+                ; A UART transmit program, bits per frame minus one are pushed before any data.
+                ; Data, parity and stop bits are encoded on the CPU, stop bits as ones.
                 ; OUT pin 0 and side-set pin 0 are both mapped to UART TX pin.
 
-                    pull       side 1 [7]  ; Assert stop bit, or stall with line in idle state
-                    set x, 7   side 0 [7]  ; Preload bit counter, assert start bit for 8 clocks
-                bitloop:                   ; This loop will run 8 times (8n1 UART)
+                    pull                   ; Load frame length once
+                    out y, 32
+                .wrap_target
+                    pull       side 1      ; Stall with line in idle state
+                    mov x, y   side 0 [7]  ; Preload bit counter, assert start bit for 8 clocks
+                bitloop:                   ; This loop will run once per bit after the start bit
                     out pins, 1            ; Shift 1 bit from OSR to the first OUT pin
                     jmp x-- bitloop   [6]  ; Each loop iteration is 8 cycles.
+                .wrap
             "#
             );
-        let tx_pin = common.make_pio_pin(tx_pin);
-        sm_tx.set_pins(gpio::Level::High, &[&tx_pin]);
-        sm_tx.set_pin_dirs(pio::Direction::Out, &[&tx_pin]);
-
         let mut cfg = pio::Config::default();
 
-        cfg.set_out_pins(&[&tx_pin]);
-        cfg.use_program(&common.load_program(&prg.program), &[&tx_pin]);
+        cfg.set_out_pins(&[tx_pin]);
+        cfg.use_program(&common.load_program(&prg.program), &[tx_pin]);
         cfg.shift_out.auto_fill = false;
         cfg.shift_out.direction = pio::ShiftDirection::Right;
         cfg.fifo_join = pio::FifoJoin::TxOnly;
-        cfg.clock_divider = (U56F8!(125_000_000) / (8 * baud)).to_fixed();
+        let baud_error = config.apply(&mut cfg)?;
         sm_tx.set_config(&cfg);
+        sm_tx.tx().push(config.framing.tx_bits() - 1);
         sm_tx.set_enable(true);
 
-        Self { sm_tx }
+        let char_time = Duration::from_micros((config.framing.tx_bits() as u64 + 1) * 1_000_000 / config.baud as u64);
+        Ok(Self { sm_tx, framing: config.framing, baud_error, char_time, line: None })
+    }
+
+    fn drive(&mut self, enabled: bool) {
+        if let Some(line) = &self.line {
+            let dir = if enabled { pio::Direction::Out } else { pio::Direction::In };
+            self.sm_tx.set_pin_dirs(dir, &[line]);
+        }
+    }
+
+    /// Relative difference between the achieved and requested baud rate.
+    pub fn baud_error(&self) -> f32 { self.baud_error }
+
+    /// Sends one character, for framings with more than 8 data bits.
+    pub async fn write_word(&mut self, data: u16) {
+        self.sm_tx.tx().wait_push(self.framing.encode(data)).await;
     }
 
     pub async fn write_u8(&mut self, data: u8) {
-        self.sm_tx.tx().wait_push(data as u32).await;
+        self.write_word(data as u16).await;
+    }
+
+    /// Encodes `buf` into `words` and pushes them through DMA, returns the number of bytes sent.
+    pub async fn write_dma<C: dma::Channel>(
+        &mut self,
+        dma: impl Peripheral<P = C>,
+        buf: &[u8],
+        words: &mut [u32],
+    ) -> usize {
+        let len = buf.len().min(words.len());
+        for (word, byte) in words.iter_mut().zip(buf) {
+            *word = self.framing.encode(*byte as u16);
+        }
+        self.sm_tx.tx().dma_push(dma.into_ref(), &words[..len]).await;
+        len
+    }
+
+    /// Waits for the last stop bit to leave the pin.
+    pub async fn wait_idle(&mut self) {
+        let _ = self.sm_tx.tx().stalled(); // clear a stale flag
+        while !self.sm_tx.tx().empty() || !self.sm_tx.tx().stalled() {
+            Timer::after(self.char_time).await;
+        }
     }
 }
 
@@ -59,10 +166,299 @@ impl<P: pio::Instance, const SM: usize> ErrorType for PioUartTx<'_, P, SM> {
 }
 
 impl<P: pio::Instance, const SM: usize> Write for PioUartTx<'_, P, SM> {
+    /// On a half duplex line, also waits for the line to be released.
     async fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+        self.drive(true);
         for byte in buf {
             self.write_u8(*byte).await;
         }
+        if self.line.is_some() {
+            self.wait_idle().await;
+            self.drive(false);
+        }
         Ok(buf.len())
     }
+
+    async fn flush(&mut self) -> Result<(), Infallible> {
+        self.wait_idle().await;
+        Ok(())
+    }
+}
+
+const FLUSHERS: usize = 4; // more concurrent flushes just cause spurious wakeups
+
+/// Ring buffer in front of a `PioUartTx`, drained through DMA by `run` in its own task.
+pub struct UartTxBuffer<const N: usize> {
+    pipe: Pipe<CriticalSectionRawMutex, N>,
+    queued: AtomicU32,
+    sent: AtomicU32,
+    drained: Mutex<CriticalSectionRawMutex, RefCell<MultiWakerRegistration<FLUSHERS>>>,
+}
+
+impl<const N: usize> UartTxBuffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            pipe: Pipe::new(),
+            queued: AtomicU32::new(0),
+            sent: AtomicU32::new(0),
+            drained: Mutex::new(RefCell::new(MultiWakerRegistration::new())),
+        }
+    }
+
+    pub fn writer(&self) -> BufferedUartTx<'_, N> { BufferedUartTx { buffer: self } }
+
+    pub async fn run<P: pio::Instance, const SM: usize, C: dma::Channel>(
+        &self,
+        mut tx: PioUartTx<'_, P, SM>,
+        dma: impl Peripheral<P = C>,
+    ) -> ! {
+        let mut dma = dma.into_ref();
+        let mut chunk = [0u8; 32];
+        let mut words = [0u32; 32];
+        let mut pushed = 0u32; // through DMA, possibly still in the FIFO or shift register
+        loop {
+            let len = self.pipe.read(&mut chunk).await;
+            tx.write_dma(dma.reborrow(), &chunk[..len], &mut words).await;
+            pushed = pushed.wrapping_add(len as u32);
+            if !self.pipe.is_empty() {
+                continue;
+            }
+            // only this task pushes, so everything counted has left the pin once idle
+            tx.wait_idle().await;
+            self.sent.fetch_add(pushed, Ordering::Release);
+            pushed = 0;
+            self.drained.lock(|wakers| wakers.borrow_mut().wake());
+        }
+    }
+}
+
+pub struct BufferedUartTx<'a, const N: usize> {
+    buffer: &'a UartTxBuffer<N>,
+}
+
+impl<const N: usize> ErrorType for BufferedUartTx<'_, N> {
+    type Error = Infallible;
+}
+
+impl<const N: usize> Write for BufferedUartTx<'_, N> {
+    /// Returns once at least one byte is queued.
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+        let len = self.buffer.pipe.write(buf).await;
+        self.buffer.queued.fetch_add(len as u32, Ordering::Release);
+        Ok(len)
+    }
+
+    /// Waits until everything queued so far has left the pin, other writers may have queued more
+    /// by then. Bytes count as sent when the transmitter next goes idle.
+    async fn flush(&mut self) -> Result<(), Infallible> {
+        let queued = self.buffer.queued.load(Ordering::Acquire);
+        poll_fn(|cx| {
+            self.buffer.drained.lock(|wakers| wakers.borrow_mut().register(cx.waker()));
+            let sent = self.buffer.sent.load(Ordering::Acquire);
+            if (sent.wrapping_sub(queued) as i32) < 0 { Poll::Pending } else { Poll::Ready(()) }
+        })
+        .await;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub enum UartError {
+    Config,
+    Framing,
+    Parity,
+    Break,
+}
+
+impl From<FrameError> for UartError {
+    fn from(op_0: FrameError) -> Self {
+        match op_0 {
+            FrameError::Framing => Self::Framing,
+            FrameError::Parity => Self::Parity,
+            FrameError::Break => Self::Break,
+        }
+    }
+}
+
+impl embedded_io_async::Error for UartError {
+    fn kind(&self) -> ErrorKind { ErrorKind::InvalidData }
+}
+
+pub struct PioUartRx<'a, P: pio::Instance, const SM: usize> {
+    sm_rx: pio::StateMachine<'a, P, SM>,
+    framing: Framing,
+    baud_error: f32,
+    pending: Option<UartError>, // hit behind bytes already returned by `read`
+}
+
+impl<'a, P: pio::Instance, const SM: usize> PioUartRx<'a, P, SM> {
+    pub fn new(
+        common: &mut pio::Common<'a, P>,
+        mut sm_rx: pio::StateMachine<'a, P, SM>,
+        rx_pin: impl pio::PioPin,
+        config: &UartConfig,
+    ) -> Result<Self, UartError> {
+        let mut rx_pin = common.make_pio_pin(rx_pin);
+        set_inverted(&rx_pin, config.inverted);
+        rx_pin.set_pull(if config.inverted { gpio::Pull::Down } else { gpio::Pull::Up });
+        sm_rx.set_pin_dirs(pio::Direction::In, &[&rx_pin]);
+        Self::from_pin(common, sm_rx, &rx_pin, config)
+    }
+
+    fn from_pin(
+        common: &mut pio::Common<'a, P>,
+        mut sm_rx: pio::StateMachine<'a, P, SM>,
+        rx_pin: &pio::Pin<'a, P>,
+        config: &UartConfig,
+    ) -> Result<Self, UartError> {
+        let prg = pio_proc::pio_asm!(
+                r#"
+                ; A UART receive program, Y holds sampled bits minus two, set before enabling.
+                ; The stop bit is pushed along with data and parity bits, so framing and
+                ; parity errors are checked on the CPU.
+                ; IN pin 0 is mapped to UART RX pin.
+
+                .wrap_target
+                    wait 0 pin 0           ; Stall until start bit is asserted
+                    mov x, y   [10]        ; Preload bit counter, then delay until halfway through
+                bitloop:                   ; the first data bit (12 cycles incl wait, mov).
+                    in pins, 1             ; Shift data and parity bits into ISR
+                    jmp x-- bitloop   [6]  ; Each loop iteration is 8 cycles
+                    in pins, 1             ; Shift stop bit into ISR
+                    push
+                    wait 1 pin 0           ; Wait out a break before looking for the next start bit
+                .wrap
+            "#
+            );
+        let mut cfg = pio::Config::default();
+
+        cfg.set_in_pins(&[rx_pin]);
+        cfg.use_program(&common.load_program(&prg.program), &[]);
+        cfg.shift_in.auto_fill = false;
+        cfg.shift_in.direction = pio::ShiftDirection::Right;
+        cfg.fifo_join = pio::FifoJoin::RxOnly; // 8 deep, at 420 kbaud that is about 190us of slack
+        let baud_error = config.apply(&mut cfg)?;
+        sm_rx.set_config(&cfg);
+        let set_y = ::pio::InstructionOperands::SET {
+            destination: ::pio::SetDestination::Y,
+            data: (config.framing.rx_bits() - 2) as u8, // at most 9, fits the 5 bit immediate
+        };
+        unsafe { sm_rx.exec_instr(set_y.encode()) }; // stopped, and nothing writes Y afterwards
+        sm_rx.set_enable(true);
+
+        Ok(Self { sm_rx, framing: config.framing, baud_error, pending: None })
+    }
+
+    pub fn baud_error(&self) -> f32 { self.baud_error }
+
+    /// Receives one character, for framings with more than 8 data bits.
+    pub async fn read_word(&mut self) -> Result<u16, UartError> {
+        let raw = self.sm_rx.rx().wait_pull().await;
+        Ok(self.framing.decode(raw)?)
+    }
+
+    pub async fn read_u8(&mut self) -> Result<u8, UartError> {
+        Ok(self.read_word().await? as u8)
+    }
+}
+
+impl<P: pio::Instance, const SM: usize> ErrorType for PioUartRx<'_, P, SM> {
+    type Error = UartError;
+}
+
+impl<P: pio::Instance, const SM: usize> Read for PioUartRx<'_, P, SM> {
+    /// Waits for at least one byte, then returns whatever else is already buffered.
+    /// An error behind good bytes is reported by the next call.
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, UartError> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if let Some(err) = self.pending.take() {
+            return Err(err);
+        }
+        buf[0] = self.read_u8().await?;
+        let mut len = 1;
+        while len < buf.len() {
+            let Some(raw) = self.sm_rx.rx().try_pull() else {
+                break;
+            };
+            match self.framing.decode(raw) {
+                Ok(data) => buf[len] = data as u8,
+                Err(err) => {
+                    self.pending = Some(err.into());
+                    break;
+                }
+            }
+            len += 1;
+        }
+        Ok(len)
+    }
+}
+
+/// Full duplex UART on two state machines of the same PIO.
+pub struct PioUart<'a, P: pio::Instance, const TX: usize, const RX: usize> {
+    tx: PioUartTx<'a, P, TX>,
+    rx: PioUartRx<'a, P, RX>,
+}
+
+impl<'a, P: pio::Instance, const TX: usize, const RX: usize> PioUart<'a, P, TX, RX> {
+    pub fn new(
+        common: &mut pio::Common<'a, P>,
+        sm_tx: pio::StateMachine<'a, P, TX>,
+        sm_rx: pio::StateMachine<'a, P, RX>,
+        tx_pin: impl pio::PioPin,
+        rx_pin: impl pio::PioPin,
+        config: &UartConfig,
+    ) -> Result<Self, UartError> {
+        let tx = PioUartTx::new(common, sm_tx, tx_pin, config)?;
+        let rx = PioUartRx::new(common, sm_rx, rx_pin, config)?;
+        Ok(Self { tx, rx })
+    }
+
+    /// Single wire UART, the line is only driven while sending. The receiver hears its own echo.
+    pub fn new_half_duplex(
+        common: &mut pio::Common<'a, P>,
+        mut sm_tx: pio::StateMachine<'a, P, TX>,
+        mut sm_rx: pio::StateMachine<'a, P, RX>,
+        pin: impl pio::PioPin,
+        config: &UartConfig,
+    ) -> Result<Self, UartError> {
+        let mut pin = common.make_pio_pin(pin);
+        set_inverted(&pin, config.inverted);
+        pin.set_pull(if config.inverted { gpio::Pull::Down } else { gpio::Pull::Up });
+        sm_tx.set_pins(gpio::Level::High, &[&pin]);
+        sm_tx.set_pin_dirs(pio::Direction::In, &[&pin]);
+        sm_rx.set_pin_dirs(pio::Direction::In, &[&pin]);
+
+        let mut tx = PioUartTx::from_pin(common, sm_tx, &pin, config)?;
+        let rx = PioUartRx::from_pin(common, sm_rx, &pin, config)?;
+        tx.line = Some(pin);
+        Ok(Self { tx, rx })
+    }
+
+    pub fn split(self) -> (PioUartTx<'a, P, TX>, PioUartRx<'a, P, RX>) {
+        (self.tx, self.rx)
+    }
+}
+
+impl<P: pio::Instance, const TX: usize, const RX: usize> ErrorType for PioUart<'_, P, TX, RX> {
+    type Error = UartError;
+}
+
+impl<P: pio::Instance, const TX: usize, const RX: usize> Read for PioUart<'_, P, TX, RX> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, UartError> {
+        self.rx.read(buf).await
+    }
+}
+
+impl<P: pio::Instance, const TX: usize, const RX: usize> Write for PioUart<'_, P, TX, RX> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, UartError> {
+        let Ok(ret) = self.tx.write(buf).await;
+        Ok(ret)
+    }
+
+    async fn flush(&mut self) -> Result<(), UartError> {
+        let Ok(()) = self.tx.flush().await;
+        Ok(())
+    }
 }
@@ -0,0 +1,35 @@
+//! Converts a thrust stand capture into CSV.
+//!
+//! Usage: `bench [INPUT]`, reads stdin when no input is given.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+
+use penguin_proto::bench::{Row, HEADER};
+
+fn main() -> io::Result<()> {
+    let input: Box<dyn BufRead> = match std::env::args().nth(1) {
+        Some(path) => Box::new(BufReader::new(File::open(path)?)),
+        None => Box::new(io::stdin().lock()),
+    };
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{}", HEADER)?;
+
+    let mut csv = String::new();
+    for (idx, line) in input.split(b'\n').enumerate() {
+        let line = line?;
+        let line = String::from_utf8_lossy(&line);
+        if !line.starts_with('$') {
+            continue; // prompts and boot noise
+        }
+        match line.parse::<Row>() {
+            Ok(row) => {
+                csv.clear();
+                row.write_csv(&mut csv).unwrap();
+                writeln!(stdout, "{}", csv)?;
+            }
+            Err(err) => eprintln!("line {}: {:?}", idx + 1, err),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,86 @@
+//! Decodes binary telemetry, printing packets or exporting them as CSV.
+//!
+//! Usage: `telemetry [--csv] [--baud BAUD] [INPUT]`, reads stdin when no input is given.
+//! With `--baud` the input is opened as a serial port, otherwise as a captured file.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+use penguin_proto::telemetry::{Decoder, HEADER};
+
+struct Args {
+    csv: bool,
+    baud: Option<u32>,
+    input: Option<String>,
+}
+
+fn args() -> Result<Args, String> {
+    let mut ret = Args { csv: false, baud: None, input: None };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--csv" => ret.csv = true,
+            "--baud" => {
+                let baud = args.next().ok_or("missing baud rate")?;
+                ret.baud = Some(baud.parse().map_err(|_| format!("bad baud rate {}", baud))?);
+            }
+            _ if ret.input.is_none() => ret.input = Some(arg),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+    if ret.baud.is_some() && ret.input.is_none() {
+        return Err("--baud needs a serial port".into());
+    }
+    Ok(ret)
+}
+
+fn open(args: &Args) -> io::Result<Box<dyn Read>> {
+    match (&args.input, args.baud) {
+        (Some(port), Some(baud)) => {
+            let port = serialport::new(port, baud).timeout(Duration::from_secs(3600)).open()?;
+            Ok(Box::new(port))
+        }
+        (Some(path), None) => Ok(Box::new(File::open(path)?)),
+        (None, _) => Ok(Box::new(io::stdin().lock())),
+    }
+}
+
+fn main() -> io::Result<()> {
+    let args = match args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }
+    };
+    let mut input = open(&args)?;
+    let mut stdout = io::stdout().lock();
+    if args.csv {
+        writeln!(stdout, "{}", HEADER)?;
+    }
+
+    let mut decoder = Decoder::new();
+    let mut buf = [0u8; 256];
+    let mut csv = String::new();
+    loop {
+        let len = match input.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(len) => len,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        for packet in buf[..len].iter().filter_map(|&b| decoder.push(b)) {
+            match packet {
+                Ok(packet) if args.csv => {
+                    csv.clear();
+                    packet.write_csv(&mut csv).unwrap();
+                    writeln!(stdout, "{}", csv)?;
+                }
+                Ok(packet) => writeln!(stdout, "{:>10} {:?}", packet.time_ms, packet.message)?,
+                Err(err) => eprintln!("dropped frame: {:?}", err),
+            }
+        }
+        stdout.flush()?;
+    }
+}
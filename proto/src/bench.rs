@@ -0,0 +1,183 @@
+//! Thrust stand rows, sent as NMEA style lines: `$BENCH,<fields>*<xor>\r\n`.
+
+use core::fmt::{self, Write};
+use core::str::FromStr;
+
+pub const HEADER: &str = "time_us,throttle,erpm,temperature_c,voltage_mv,current_a,thrust_g";
+const TAG: &str = "BENCH";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Row {
+    pub time_us: u64,
+    pub throttle: u16,
+    pub erpm: Option<u32>,
+    pub temperature: Option<u8>,
+    pub voltage_mv: Option<u32>,
+    pub current: Option<u8>,
+    pub thrust: f32, // grams
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowError {
+    Framing,
+    Checksum,
+    Field,
+}
+
+struct Checksum<'a, W: Write> {
+    inner: &'a mut W,
+    crc: u8,
+}
+
+impl<W: Write> Write for Checksum<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.crc = s.bytes().fold(self.crc, |acc, b| acc ^ b);
+        self.inner.write_str(s)
+    }
+}
+
+fn checksum(s: &str) -> u8 { s.bytes().fold(0, |acc, b| acc ^ b) }
+
+struct Opt<T>(Option<T>);
+
+impl<T: fmt::Display> fmt::Display for Opt<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Some(v) => v.fmt(f),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Row {
+    /// Writes the row as a single line, including checksum and line ending.
+    pub fn write<W: Write>(&self, w: &mut W) -> fmt::Result {
+        w.write_char('$')?;
+        let mut body = Checksum { inner: w, crc: 0 };
+        write!(body, "{},", TAG)?;
+        self.write_csv(&mut body)?;
+        let crc = body.crc;
+        write!(w, "*{:02X}\r\n", crc)
+    }
+
+    /// Writes the fields matching `HEADER`, without line ending.
+    pub fn write_csv<W: Write>(&self, w: &mut W) -> fmt::Result {
+        write!(
+            w,
+            "{},{},{},{},{},{},{:.1}",
+            self.time_us,
+            self.throttle,
+            Opt(self.erpm),
+            Opt(self.temperature),
+            Opt(self.voltage_mv),
+            Opt(self.current),
+            self.thrust,
+        )
+    }
+}
+
+fn field<T: FromStr>(s: Option<&str>) -> Result<T, RowError> {
+    s.ok_or(RowError::Field)?.parse().map_err(|_| RowError::Field)
+}
+
+fn opt_field<T: FromStr>(s: Option<&str>) -> Result<Option<T>, RowError> {
+    match s {
+        Some("") => Ok(None),
+        s => field(s).map(Some),
+    }
+}
+
+impl FromStr for Row {
+    type Err = RowError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.trim_end();
+        let line = line.strip_prefix('$').ok_or(RowError::Framing)?;
+        let (body, crc) = line.split_once('*').ok_or(RowError::Framing)?;
+        let crc = u8::from_str_radix(crc, 16).map_err(|_| RowError::Framing)?;
+        if checksum(body) != crc {
+            return Err(RowError::Checksum);
+        }
+
+        let mut fields = body.split(',');
+        if fields.next() != Some(TAG) {
+            return Err(RowError::Framing);
+        }
+        let ret = Self {
+            time_us: field(fields.next())?,
+            throttle: field(fields.next())?,
+            erpm: opt_field(fields.next())?,
+            temperature: opt_field(fields.next())?,
+            voltage_mv: opt_field(fields.next())?,
+            current: opt_field(fields.next())?,
+            thrust: field(fields.next())?,
+        };
+        if fields.next().is_some() {
+            return Err(RowError::Field);
+        }
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::string::String;
+
+    use super::*;
+
+    fn full() -> Row {
+        Row {
+            time_us: 1000,
+            throttle: 240,
+            erpm: Some(12_000),
+            temperature: Some(31),
+            voltage_mv: Some(12_400),
+            current: Some(3),
+            thrust: 152.5,
+        }
+    }
+
+    fn line(row: &Row) -> String {
+        let mut ret = String::new();
+        row.write(&mut ret).unwrap();
+        ret
+    }
+
+    #[test]
+    fn known_lines() {
+        assert_eq!(line(&full()), "$BENCH,1000,240,12000,31,12400,3,152.5*41\r\n");
+        let empty = Row { time_us: 5, ..Default::default() };
+        assert_eq!(line(&empty), "$BENCH,5,0,,,,,0.0*45\r\n");
+    }
+
+    #[test]
+    fn round_trip() {
+        for row in [full(), Row::default(), Row { erpm: Some(0), thrust: -3.24, ..full() }] {
+            let parsed: Row = line(&row).parse().unwrap();
+            assert_eq!(parsed, Row { thrust: (row.thrust * 10.0).round() / 10.0, ..row });
+        }
+        // host side tolerates a missing carriage return
+        assert_eq!("$BENCH,5,0,,,,,0.0*45\n".parse(), Ok(Row { time_us: 5, ..Default::default() }));
+    }
+
+    #[test]
+    fn malformed() {
+        let cases = [
+            ("BENCH,5,0,,,,,0.0*45", RowError::Framing), // no start
+            ("$BENCH,5,0,,,,,0.0", RowError::Framing), // no checksum
+            ("$BENCH,5,0,,,,,0.0*4G", RowError::Framing),
+            ("$BENCH,5,0,,,,,0.0*44", RowError::Checksum),
+            ("$BENCH,6,0,,,,,0.0*45", RowError::Checksum), // corrupted field
+            ("$BENCX,5,0,,,,,0.0*55", RowError::Framing),
+            ("$BENCH,5,0,,,,*47", RowError::Field), // missing thrust
+            ("$BENCH,5,0,,,,,0.0,1*58", RowError::Field), // extra field
+            ("$BENCH,5,x,,,,,0.0*0D", RowError::Field),
+            ("$BENCH,5,0,,,,,*6B", RowError::Field), // thrust is not optional
+        ];
+        for (line, err) in cases {
+            assert_eq!(line.parse::<Row>(), Err(err), "{}", line);
+        }
+    }
+}
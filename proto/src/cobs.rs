@@ -0,0 +1,106 @@
+//! Consistent overhead byte stuffing, frames are delimited by a zero byte.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CobsError {
+    Overflow,
+    Malformed,
+}
+
+/// Worst case encoded length, without the delimiter.
+pub const fn max_encoded_len(len: usize) -> usize { len + len / 254 + 1 }
+
+/// Encodes `data` into `out`, without the delimiter.
+pub fn encode(data: &[u8], out: &mut [u8]) -> Result<usize, CobsError> {
+    let mut code_idx = 0;
+    let mut idx = 1;
+    let mut code = 1u8;
+    for &b in data {
+        if b != 0 {
+            *out.get_mut(idx).ok_or(CobsError::Overflow)? = b;
+            idx += 1;
+            code += 1;
+        }
+        if b == 0 || code == 0xFF {
+            *out.get_mut(code_idx).ok_or(CobsError::Overflow)? = code;
+            code_idx = idx;
+            idx += 1;
+            code = 1;
+        }
+    }
+    *out.get_mut(code_idx).ok_or(CobsError::Overflow)? = code;
+    Ok(idx)
+}
+
+/// Decodes a frame in place, without the delimiter, returns the decoded length.
+pub fn decode_in_place(buf: &mut [u8]) -> Result<usize, CobsError> {
+    let mut src = 0;
+    let mut dst = 0;
+    while src < buf.len() {
+        let code = buf[src] as usize;
+        if code == 0 || src + code > buf.len() {
+            return Err(CobsError::Malformed);
+        }
+        buf.copy_within(src + 1..src + code, dst);
+        dst += code - 1;
+        src += code;
+        if code != 0xFF && src < buf.len() {
+            buf[dst] = 0;
+            dst += 1;
+        }
+    }
+    Ok(dst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(data: &[u8]) {
+        let mut buf = [0u8; 1024];
+        let len = encode(data, &mut buf).unwrap();
+        assert!(len <= max_encoded_len(data.len()));
+        assert!(!buf[..len].contains(&0));
+        assert_eq!(decode_in_place(&mut buf[..len]), Ok(data.len()));
+        assert_eq!(&buf[..data.len()], data);
+    }
+
+    #[test]
+    fn known_vectors() {
+        let vectors: [(&[u8], &[u8]); 5] = [
+            (&[], &[0x01]),
+            (&[0x00], &[0x01, 0x01]),
+            (&[0x00, 0x00], &[0x01, 0x01, 0x01]),
+            (&[0x11, 0x22, 0x00, 0x33], &[0x03, 0x11, 0x22, 0x02, 0x33]),
+            (&[0x11, 0x00, 0x00, 0x00], &[0x02, 0x11, 0x01, 0x01, 0x01]),
+        ];
+        for (data, encoded) in vectors {
+            let mut buf = [0u8; 8];
+            let len = encode(data, &mut buf).unwrap();
+            assert_eq!(&buf[..len], encoded);
+            assert_eq!(decode_in_place(&mut buf[..len]), Ok(data.len()));
+            assert_eq!(&buf[..data.len()], data);
+        }
+    }
+
+    #[test]
+    fn long_runs() {
+        let mut data = [0u8; 600];
+        for (idx, b) in data.iter_mut().enumerate() {
+            *b = (idx % 255 + 1) as u8;
+        }
+        for len in [253, 254, 255, 508, 600] {
+            round_trip(&data[..len]);
+        }
+        data[254] = 0;
+        data[300] = 0;
+        round_trip(&data);
+    }
+
+    #[test]
+    fn errors() {
+        let mut buf = [0u8; 4];
+        assert_eq!(encode(&[1, 2, 3, 4], &mut buf), Err(CobsError::Overflow));
+        assert_eq!(decode_in_place(&mut [0x03, 0x11]), Err(CobsError::Malformed));
+        assert_eq!(decode_in_place(&mut [0x02, 0x11, 0x00]), Err(CobsError::Malformed));
+    }
+}
@@ -0,0 +1,56 @@
+//! Bitwise CRCs, slow but table free.
+
+/// CRC-16/CCITT-FALSE, poly 0x1021, init 0xFFFF.
+pub fn crc16(data: &[u8]) -> u16 { crc16_update(0xFFFF, data) }
+
+pub fn crc16_update(crc: u16, data: &[u8]) -> u16 {
+    data.iter().fold(crc, |crc, &b| {
+        (0..8).fold(crc ^ (b as u16) << 8, |crc, _| {
+            if crc & 0x8000 != 0 { crc << 1 ^ 0x1021 } else { crc << 1 }
+        })
+    })
+}
+
+/// CRC-8/DVB-S2, poly 0xD5, used by MSP v2 and CRSF.
+pub fn crc8_dvb_s2(data: &[u8]) -> u8 { crc8_dvb_s2_update(0, data) }
+
+pub fn crc8_dvb_s2_update(crc: u8, data: &[u8]) -> u8 {
+    data.iter().fold(crc, |crc, &b| {
+        (0..8).fold(crc ^ b, |crc, _| {
+            if crc & 0x80 != 0 { crc << 1 ^ 0xD5 } else { crc << 1 }
+        })
+    })
+}
+
+/// CRC-16/MCRF4XX, the X.25 checksum used by MAVLink.
+pub fn crc16_mcrf4xx(data: &[u8]) -> u16 { crc16_mcrf4xx_update(0xFFFF, data) }
+
+pub fn crc16_mcrf4xx_update(crc: u16, data: &[u8]) -> u16 {
+    data.iter().fold(crc, |crc, &b| {
+        let tmp = b ^ crc as u8;
+        let tmp = (tmp ^ tmp << 4) as u16;
+        crc >> 8 ^ tmp << 8 ^ tmp << 3 ^ tmp >> 4
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHECK: &[u8] = b"123456789";
+
+    #[test]
+    fn check_values() {
+        assert_eq!(crc16(CHECK), 0x29B1);
+        assert_eq!(crc8_dvb_s2(CHECK), 0xBC);
+        assert_eq!(crc16_mcrf4xx(CHECK), 0x6F91);
+    }
+
+    #[test]
+    fn incremental() {
+        let (a, b) = CHECK.split_at(4);
+        assert_eq!(crc16_update(crc16(a), b), crc16(CHECK));
+        assert_eq!(crc8_dvb_s2_update(crc8_dvb_s2(a), b), crc8_dvb_s2(CHECK));
+        assert_eq!(crc16_mcrf4xx_update(crc16_mcrf4xx(a), b), crc16_mcrf4xx(CHECK));
+    }
+}
@@ -0,0 +1,316 @@
+//! Crossfire serial protocol as spoken by ExpressLRS receivers.
+//! Frames are `[address, length, type, payload.., crc8]`, the CRC covers type and payload.
+
+use crate::crc::crc8_dvb_s2;
+use crate::rc::{self, RcChannels};
+
+pub const BAUD: u32 = 420_000;
+pub const MAX_FRAME: usize = 64;
+const MAX_PAYLOAD: usize = MAX_FRAME - 4;
+
+pub mod address {
+    pub const BROADCAST: u8 = 0x00;
+    pub const FLIGHT_CONTROLLER: u8 = 0xC8;
+    pub const RADIO_TRANSMITTER: u8 = 0xEA;
+    pub const RECEIVER: u8 = 0xEC;
+    pub const TRANSMITTER: u8 = 0xEE;
+}
+
+pub mod frame_type {
+    pub const BATTERY_SENSOR: u8 = 0x08;
+    pub const LINK_STATISTICS: u8 = 0x14;
+    pub const RC_CHANNELS_PACKED: u8 = 0x16;
+    pub const ATTITUDE: u8 = 0x1E;
+    pub const DEVICE_PING: u8 = 0x28;
+    pub const DEVICE_INFO: u8 = 0x29;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CrsfError {
+    Length,
+    Checksum,
+    Overflow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LinkStatistics {
+    pub uplink_rssi: [i16; 2], // dBm, per antenna
+    pub uplink_lq: u8, // %
+    pub uplink_snr: i8, // dB
+    pub active_antenna: u8,
+    pub rf_mode: u8,
+    pub uplink_tx_power: u8, // enumerated, 0 for 0 mW
+    pub downlink_rssi: i16, // dBm
+    pub downlink_lq: u8,
+    pub downlink_snr: i8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Packet {
+    RcChannels(RcChannels),
+    LinkStatistics(LinkStatistics),
+    DevicePing { destination: u8, origin: u8 },
+    Other(u8), // frame type
+}
+
+impl Packet {
+    fn parse(kind: u8, payload: &[u8]) -> Result<Self, CrsfError> {
+        let ret = match kind {
+            frame_type::RC_CHANNELS_PACKED => {
+                let data: &[u8; 22] = payload.try_into().map_err(|_| CrsfError::Length)?;
+                let mut channels = RcChannels { count: 16, ..Default::default() };
+                for (us, ticks) in channels.us.iter_mut().zip(rc::unpack_11bit(data)) {
+                    *us = rc::ticks_to_us(ticks);
+                }
+                Self::RcChannels(channels)
+            }
+            frame_type::LINK_STATISTICS => {
+                let p: &[u8; 10] = payload.try_into().map_err(|_| CrsfError::Length)?;
+                Self::LinkStatistics(LinkStatistics {
+                    uplink_rssi: [-(p[0] as i16), -(p[1] as i16)],
+                    uplink_lq: p[2],
+                    uplink_snr: p[3] as i8,
+                    active_antenna: p[4],
+                    rf_mode: p[5],
+                    uplink_tx_power: p[6],
+                    downlink_rssi: -(p[7] as i16),
+                    downlink_lq: p[8],
+                    downlink_snr: p[9] as i8,
+                })
+            }
+            frame_type::DEVICE_PING => {
+                let [destination, origin, ..] = *payload else {
+                    return Err(CrsfError::Length);
+                };
+                Self::DevicePing { destination, origin }
+            }
+            kind => Self::Other(kind),
+        };
+        Ok(ret)
+    }
+}
+
+/// Byte at a time frame parser, resynchronises on the address byte.
+pub struct Parser {
+    buf: [u8; MAX_FRAME],
+    len: usize,
+}
+
+impl Default for Parser {
+    fn default() -> Self { Self::new() }
+}
+
+impl Parser {
+    pub const fn new() -> Self { Self { buf: [0; MAX_FRAME], len: 0 } }
+
+    pub fn reset(&mut self) { self.len = 0; }
+
+    pub fn push(&mut self, b: u8) -> Option<Result<Packet, CrsfError>> {
+        match self.len {
+            0 if !matches!(b, address::FLIGHT_CONTROLLER | address::BROADCAST | address::RADIO_TRANSMITTER) => return None,
+            1 if !(2..=MAX_FRAME as u8 - 2).contains(&b) => {
+                self.len = 0;
+                return Some(Err(CrsfError::Length));
+            }
+            _ => {}
+        }
+        self.buf[self.len] = b;
+        self.len += 1;
+        if self.len < 2 || self.len < self.buf[1] as usize + 2 {
+            return None;
+        }
+
+        let frame = &self.buf[..self.len];
+        self.len = 0;
+        let (body, crc) = frame[2..].split_at(frame.len() - 3);
+        if crc8_dvb_s2(body) != crc[0] {
+            return Some(Err(CrsfError::Checksum));
+        }
+        Some(Packet::parse(body[0], &body[1..]))
+    }
+}
+
+/// Writes a frame addressed to `address`, returns its length.
+pub fn encode(address: u8, kind: u8, payload: &[u8], out: &mut [u8]) -> Result<usize, CrsfError> {
+    let len = payload.len() + 4;
+    if payload.len() > MAX_PAYLOAD || out.len() < len {
+        return Err(CrsfError::Overflow);
+    }
+    out[0] = address;
+    out[1] = payload.len() as u8 + 2;
+    out[2] = kind;
+    out[3..len - 1].copy_from_slice(payload);
+    out[len - 1] = crc8_dvb_s2(&out[2..len - 1]);
+    Ok(len)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Battery {
+    pub voltage_dv: u16, // 0.1 V
+    pub current_da: u16, // 0.1 A
+    pub consumed_mah: u32, // 24 bit on the wire
+    pub remaining: u8, // %
+}
+
+impl Battery {
+    pub fn encode(&self, out: &mut [u8]) -> Result<usize, CrsfError> {
+        let v = self.voltage_dv.to_be_bytes();
+        let c = self.current_da.to_be_bytes();
+        let mah = self.consumed_mah.min(0xFF_FFFF).to_be_bytes();
+        let payload = [v[0], v[1], c[0], c[1], mah[1], mah[2], mah[3], self.remaining];
+        encode(address::FLIGHT_CONTROLLER, frame_type::BATTERY_SENSOR, &payload, out)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Attitude {
+    pub pitch: f32, // rad
+    pub roll: f32,
+    pub yaw: f32,
+}
+
+impl Attitude {
+    pub fn encode(&self, out: &mut [u8]) -> Result<usize, CrsfError> {
+        let scale = |rad: f32| ((rad * 10_000.0) as i16).to_be_bytes();
+        let (p, r, y) = (scale(self.pitch), scale(self.roll), scale(self.yaw));
+        let payload = [p[0], p[1], r[0], r[1], y[0], y[1]];
+        encode(address::FLIGHT_CONTROLLER, frame_type::ATTITUDE, &payload, out)
+    }
+}
+
+/// Answer to a device ping, `name` is sent null terminated.
+pub fn encode_device_info(destination: u8, name: &str, out: &mut [u8]) -> Result<usize, CrsfError> {
+    let mut payload = [0u8; MAX_PAYLOAD];
+    let name = name.as_bytes();
+    let len = 2 + name.len() + 1 + 14;
+    if len > MAX_PAYLOAD {
+        return Err(CrsfError::Overflow);
+    }
+    payload[0] = destination;
+    payload[1] = address::FLIGHT_CONTROLLER;
+    payload[2..2 + name.len()].copy_from_slice(name);
+    // serial, hardware and software version stay zero, no parameters, protocol 0
+    encode(address::FLIGHT_CONTROLLER, frame_type::DEVICE_INFO, &payload[..len], out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// All sixteen channels at 992, as sent by a radio with centred sticks.
+    const CENTERED: [u8; 26] = [
+        0xC8, 0x18, 0x16, 0xE0, 0x03, 0x1F, 0xF8, 0xC0, 0x07, 0x3E, 0xF0, 0x81, 0x0F, 0x7C, 0xE0, 0x03, 0x1F,
+        0xF8, 0xC0, 0x07, 0x3E, 0xF0, 0x81, 0x0F, 0x7C, 0xAD,
+    ];
+
+    /// Link statistics, hand assembled: -50/-60 dBm, LQ 100, SNR 12, antenna 1, mode 4,
+    /// power 3, downlink -70 dBm, LQ 98, SNR -10.
+    const LINK: [u8; 14] = [0xC8, 0x0C, 0x14, 50, 60, 100, 12, 1, 4, 3, 70, 98, 0xF6, 0x65];
+
+    fn parse(parser: &mut Parser, bytes: &[u8]) -> Option<Result<Packet, CrsfError>> {
+        let mut ret = None;
+        for &b in bytes {
+            if let Some(packet) = parser.push(b) {
+                assert!(ret.is_none(), "more than one frame");
+                ret = Some(packet);
+            }
+        }
+        ret
+    }
+
+    #[test]
+    fn rc_channels() {
+        let mut parser = Parser::new();
+        let Some(Ok(Packet::RcChannels(channels))) = parse(&mut parser, &CENTERED) else {
+            panic!("no channels");
+        };
+        assert_eq!(channels.as_slice(), &[rc::MID_US; 16]);
+
+        let ticks = [172, 992, 1811, 0, 2047, 1, 1024, 500, 600, 700, 800, 900, 1000, 1100, 1200, 1300];
+        let mut frame = [0u8; MAX_FRAME];
+        let len = encode(address::FLIGHT_CONTROLLER, frame_type::RC_CHANNELS_PACKED, &rc::pack_11bit(&ticks), &mut frame)
+            .unwrap();
+        assert_eq!(frame[len - 1], 0xDE);
+        let Some(Ok(Packet::RcChannels(channels))) = parse(&mut parser, &frame[..len]) else {
+            panic!("no channels");
+        };
+        assert_eq!(channels.get(0), Some(988));
+        assert_eq!(channels.get(1), Some(1500));
+        assert_eq!(channels.get(2), Some(2011));
+        assert_eq!(channels.get(15), Some(rc::ticks_to_us(1300)));
+    }
+
+    #[test]
+    fn link_statistics() {
+        let expected = LinkStatistics {
+            uplink_rssi: [-50, -60],
+            uplink_lq: 100,
+            uplink_snr: 12,
+            active_antenna: 1,
+            rf_mode: 4,
+            uplink_tx_power: 3,
+            downlink_rssi: -70,
+            downlink_lq: 98,
+            downlink_snr: -10,
+        };
+        assert_eq!(parse(&mut Parser::new(), &LINK), Some(Ok(Packet::LinkStatistics(expected))));
+    }
+
+    #[test]
+    fn device_ping() {
+        let mut frame = [0u8; MAX_FRAME];
+        let len = encode(address::BROADCAST, frame_type::DEVICE_PING, &[0x00, address::RADIO_TRANSMITTER], &mut frame)
+            .unwrap();
+        let packet = parse(&mut Parser::new(), &frame[..len]);
+        assert_eq!(packet, Some(Ok(Packet::DevicePing { destination: 0x00, origin: address::RADIO_TRANSMITTER })));
+
+        let len = encode_device_info(address::RADIO_TRANSMITTER, "penguin", &mut frame).unwrap();
+        assert_eq!(len, 4 + 2 + 8 + 14);
+        assert_eq!(&frame[..3], &[address::FLIGHT_CONTROLLER, len as u8 - 2, frame_type::DEVICE_INFO]);
+        assert_eq!(&frame[3..5], &[address::RADIO_TRANSMITTER, address::FLIGHT_CONTROLLER]);
+        assert_eq!(&frame[5..13], b"penguin\0");
+        assert_eq!(parse(&mut Parser::new(), &frame[..len]), Some(Ok(Packet::Other(frame_type::DEVICE_INFO))));
+        let name = "a name much too long to fit into one frame with the rest";
+        assert_eq!(encode_device_info(0, name, &mut frame), Err(CrsfError::Overflow));
+    }
+
+    #[test]
+    fn telemetry() {
+        let mut frame = [0u8; MAX_FRAME];
+        let battery = Battery { voltage_dv: 126, current_da: 153, consumed_mah: 0x0123_4567, remaining: 80 };
+        let len = battery.encode(&mut frame).unwrap();
+        assert_eq!(&frame[..len - 1], &[0xC8, 10, 0x08, 0, 126, 0, 153, 0xFF, 0xFF, 0xFF, 80]);
+        assert_eq!(parse(&mut Parser::new(), &frame[..len]), Some(Ok(Packet::Other(frame_type::BATTERY_SENSOR))));
+
+        let attitude = Attitude { pitch: 0.5, roll: -0.25, yaw: 3.0 };
+        let len = attitude.encode(&mut frame).unwrap();
+        assert_eq!(&frame[..len - 1], &[0xC8, 8, 0x1E, 0x13, 0x88, 0xF6, 0x3C, 0x75, 0x30]);
+        assert_eq!(parse(&mut Parser::new(), &frame[..len]), Some(Ok(Packet::Other(frame_type::ATTITUDE))));
+    }
+
+    #[test]
+    fn rejects_corruption() {
+        let mut frame = CENTERED;
+        frame[10] ^= 0x10;
+        assert_eq!(parse(&mut Parser::new(), &frame), Some(Err(CrsfError::Checksum)));
+
+        let mut frame = LINK;
+        frame[1] = 0x0B; // one byte short, so the payload no longer matches the type
+        frame[12] = crc8_dvb_s2(&frame[2..12]);
+        assert_eq!(parse(&mut Parser::new(), &frame[..13]), Some(Err(CrsfError::Length)));
+
+        assert_eq!(parse(&mut Parser::new(), &[0xC8, 0x01]), Some(Err(CrsfError::Length)));
+        assert_eq!(parse(&mut Parser::new(), &[0xC8, MAX_FRAME as u8 - 1]), Some(Err(CrsfError::Length)));
+    }
+
+    #[test]
+    fn resyncs() {
+        let mut parser = Parser::new();
+        assert_eq!(parse(&mut parser, &[0x55, 0xEC, 0x13, 0x37]), None); // not an address we listen to
+        let mut frame = CENTERED;
+        frame[20] ^= 0x01;
+        assert_eq!(parse(&mut parser, &frame), Some(Err(CrsfError::Checksum)));
+        assert!(matches!(parse(&mut parser, &CENTERED), Some(Ok(Packet::RcChannels(_)))));
+        assert!(matches!(parse(&mut parser, &LINK), Some(Ok(Packet::LinkStatistics(_)))));
+    }
+}
@@ -0,0 +1,174 @@
+//! Single button gestures, fed with debounced edges and millisecond timestamps.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    Click,
+    DoubleClick,
+    LongPress(u64), // press duration in ms
+    Hold, // still pressed after `hold_ms`
+    Release, // released after a `Hold`
+}
+
+#[derive(Debug, Clone)]
+pub struct GestureConfig {
+    pub click_ms: u64, // longest press counted as a click
+    pub double_click_ms: u64, // longest gap between the clicks of a double click
+    pub long_press_ms: u64, // shortest press counted as a long press
+    pub hold_ms: u64, // press length reported as `Hold` before release
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        Self { click_ms: 300, double_click_ms: 300, long_press_ms: 800, hold_ms: 2000 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+    Pressed { since: u64, second: bool },
+    Held,
+    Clicked { at: u64 },
+}
+
+pub struct Gestures {
+    config: GestureConfig,
+    state: State,
+}
+
+impl Gestures {
+    pub fn new(config: GestureConfig) -> Self { Self { config, state: State::Idle } }
+
+    pub fn set_config(&mut self, config: GestureConfig) { self.config = config; }
+
+    /// Time at which `timeout` has to be called, if anything is pending.
+    pub fn deadline(&self) -> Option<u64> {
+        match self.state {
+            State::Pressed { since, second: true } => Some(since + self.config.click_ms),
+            State::Pressed { since, .. } => Some(since + self.config.hold_ms),
+            State::Clicked { at } => Some(at + self.config.double_click_ms),
+            State::Idle | State::Held => None,
+        }
+    }
+
+    pub fn edge(&mut self, pressed: bool, now: u64) -> Option<Gesture> {
+        let (state, ret) = match (self.state, pressed) {
+            (State::Idle, true) => (State::Pressed { since: now, second: false }, None),
+            (State::Clicked { .. }, true) => (State::Pressed { since: now, second: true }, None),
+            (State::Pressed { since, second }, false) => {
+                let duration = now - since;
+                if duration <= self.config.click_ms {
+                    if second {
+                        (State::Idle, Some(Gesture::DoubleClick))
+                    } else {
+                        (State::Clicked { at: now }, None)
+                    }
+                } else if second {
+                    (State::Idle, Some(Gesture::Click)) // `timeout` was late, the second press is dropped
+                } else if duration >= self.config.long_press_ms {
+                    (State::Idle, Some(Gesture::LongPress(duration)))
+                } else {
+                    (State::Idle, None)
+                }
+            }
+            (State::Held, false) => (State::Idle, Some(Gesture::Release)),
+            (state, _) => (state, None), // repeated level, nothing changed
+        };
+        self.state = state;
+        ret
+    }
+
+    pub fn timeout(&mut self, now: u64) -> Option<Gesture> {
+        match self.deadline() {
+            Some(deadline) if now >= deadline => {}
+            _ => return None,
+        }
+        let (state, ret) = match self.state {
+            // too long for a double click, the first press was a click and this one starts over
+            State::Pressed { since, second: true } => (State::Pressed { since, second: false }, Gesture::Click),
+            State::Pressed { .. } => (State::Held, Gesture::Hold),
+            _ => (State::Idle, Gesture::Click),
+        };
+        self.state = state;
+        Some(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(events: &[(u64, Option<bool>)]) -> ([Option<Gesture>; 8], usize) {
+        let mut gestures = Gestures::new(GestureConfig::default());
+        let mut ret = [None; 8];
+        let mut len = 0;
+        for &(now, pressed) in events {
+            while gestures.deadline().is_some_and(|deadline| deadline <= now) {
+                if let Some(gesture) = gestures.timeout(now) {
+                    ret[len] = Some(gesture);
+                    len += 1;
+                }
+            }
+            if let Some(gesture) = pressed.and_then(|pressed| gestures.edge(pressed, now)) {
+                ret[len] = Some(gesture);
+                len += 1;
+            }
+        }
+        (ret, len)
+    }
+
+    fn assert_gestures(events: &[(u64, Option<bool>)], expected: &[Gesture]) {
+        let (ret, len) = run(events);
+        assert_eq!(len, expected.len(), "{:?}", ret);
+        for (got, want) in ret.iter().zip(expected) {
+            assert_eq!(got, &Some(*want));
+        }
+    }
+
+    #[test]
+    fn click() {
+        assert_gestures(&[(0, Some(true)), (100, Some(false)), (1000, None)], &[Gesture::Click]);
+    }
+
+    #[test]
+    fn double_click() {
+        let events = [(0, Some(true)), (100, Some(false)), (200, Some(true)), (300, Some(false)), (2000, None)];
+        assert_gestures(&events, &[Gesture::DoubleClick]);
+    }
+
+    #[test]
+    fn long_press() {
+        assert_gestures(&[(0, Some(true)), (1000, Some(false))], &[Gesture::LongPress(1000)]);
+        assert_gestures(&[(0, Some(true)), (500, Some(false)), (2000, None)], &[]);
+    }
+
+    #[test]
+    fn hold() {
+        let events = [(0, Some(true)), (2500, None), (3000, Some(false))];
+        assert_gestures(&events, &[Gesture::Hold, Gesture::Release]);
+    }
+
+    #[test]
+    fn slow_second_press() {
+        let events = [(0, Some(true)), (100, Some(false)), (200, Some(true)), (700, Some(false)), (2000, None)];
+        assert_gestures(&events, &[Gesture::Click]);
+        let events = [(0, Some(true)), (100, Some(false)), (200, Some(true)), (1200, Some(false))];
+        assert_gestures(&events, &[Gesture::Click, Gesture::LongPress(1000)]);
+        let events = [(0, Some(true)), (100, Some(false)), (200, Some(true)), (2500, None), (3000, Some(false))];
+        assert_gestures(&events, &[Gesture::Click, Gesture::Hold, Gesture::Release]);
+
+        // release seen before the overdue timeout
+        let mut gestures = Gestures::new(GestureConfig::default());
+        gestures.edge(true, 0);
+        gestures.edge(false, 100);
+        gestures.edge(true, 200);
+        assert_eq!(gestures.edge(false, 700), Some(Gesture::Click));
+        assert_eq!(gestures.deadline(), None);
+    }
+
+    #[test]
+    fn repeated_level() {
+        let events = [(0, Some(true)), (50, Some(true)), (100, Some(false)), (150, Some(false)), (1000, None)];
+        assert_gestures(&events, &[Gesture::Click]);
+    }
+}
@@ -0,0 +1,15 @@
+#![no_std]
+
+pub mod bench;
+pub mod uart;
+pub mod gesture;
+pub mod crc;
+pub mod cobs;
+pub mod telemetry;
+pub mod msp;
+pub mod mavlink;
+pub mod rc;
+pub mod crsf;
+pub mod sbus;
+pub mod ppm;
+pub mod store;
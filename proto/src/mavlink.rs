@@ -0,0 +1,612 @@
+//! MAVLink v2 framing and the handful of common dialect messages we speak.
+//! Signed frames are accepted without checking the signature.
+
+use crate::crc::crc16_mcrf4xx_update;
+
+pub const STX: u8 = 0xFD;
+pub const HEADER_LEN: usize = 10;
+pub const MAX_PAYLOAD: usize = 255;
+const SIGNATURE_LEN: usize = 13;
+const FLAG_SIGNED: u8 = 0x01;
+/// Largest frame we emit, unsigned.
+pub const MAX_FRAME: usize = HEADER_LEN + MAX_PAYLOAD + 2;
+
+pub mod mav_type {
+    pub const QUADROTOR: u8 = 2;
+    pub const GCS: u8 = 6;
+}
+
+pub mod mode_flag {
+    pub const CUSTOM_MODE_ENABLED: u8 = 0x01;
+    pub const SAFETY_ARMED: u8 = 0x80;
+}
+
+pub mod state {
+    pub const BOOT: u8 = 1;
+    pub const STANDBY: u8 = 3;
+    pub const ACTIVE: u8 = 4;
+    pub const CRITICAL: u8 = 5;
+}
+
+pub mod command {
+    pub const DO_MOTOR_TEST: u16 = 209;
+    pub const COMPONENT_ARM_DISARM: u16 = 400;
+}
+
+pub mod result {
+    pub const ACCEPTED: u8 = 0;
+    pub const TEMPORARILY_REJECTED: u8 = 1;
+    pub const DENIED: u8 = 2;
+    pub const UNSUPPORTED: u8 = 3;
+    pub const FAILED: u8 = 4;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MavError {
+    Overflow, // output buffer too small
+    Framing, // missing magic byte or shorter than its length field
+    Checksum,
+    Unknown(u32), // message id without a known CRC_EXTRA
+}
+
+struct Writer<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl Writer<'_> {
+    fn bytes(&mut self, b: &[u8]) -> &mut Self {
+        self.buf[self.len..self.len + b.len()].copy_from_slice(b);
+        self.len += b.len();
+        self
+    }
+
+    fn u8(&mut self, v: u8) -> &mut Self { self.bytes(&[v]) }
+    fn u16(&mut self, v: u16) -> &mut Self { self.bytes(&v.to_le_bytes()) }
+    fn i16(&mut self, v: i16) -> &mut Self { self.bytes(&v.to_le_bytes()) }
+    fn u32(&mut self, v: u32) -> &mut Self { self.bytes(&v.to_le_bytes()) }
+    fn i32(&mut self, v: i32) -> &mut Self { self.bytes(&v.to_le_bytes()) }
+    fn u64(&mut self, v: u64) -> &mut Self { self.bytes(&v.to_le_bytes()) }
+    fn f32(&mut self, v: f32) -> &mut Self { self.bytes(&v.to_le_bytes()) }
+}
+
+/// Reads from a payload zero extended to the full message length, as v2 truncates trailing zeros.
+struct Reader {
+    buf: [u8; MAX_PAYLOAD],
+    pos: usize,
+}
+
+impl Reader {
+    fn new(payload: &[u8]) -> Self {
+        let mut buf = [0; MAX_PAYLOAD];
+        buf[..payload.len()].copy_from_slice(payload);
+        Self { buf, pos: 0 }
+    }
+
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let mut ret = [0; N];
+        ret.copy_from_slice(&self.buf[self.pos..self.pos + N]);
+        self.pos += N;
+        ret
+    }
+
+    fn u8(&mut self) -> u8 { self.take::<1>()[0] }
+    fn i8(&mut self) -> i8 { self.u8() as i8 }
+    fn u16(&mut self) -> u16 { u16::from_le_bytes(self.take()) }
+    fn i16(&mut self) -> i16 { i16::from_le_bytes(self.take()) }
+    fn u32(&mut self) -> u32 { u32::from_le_bytes(self.take()) }
+    fn i32(&mut self) -> i32 { i32::from_le_bytes(self.take()) }
+    fn u64(&mut self) -> u64 { u64::from_le_bytes(self.take()) }
+    fn f32(&mut self) -> f32 { f32::from_le_bytes(self.take()) }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Heartbeat {
+    pub custom_mode: u32,
+    pub mav_type: u8,
+    pub autopilot: u8, // 0, generic
+    pub base_mode: u8,
+    pub system_status: u8,
+    pub mavlink_version: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SysStatus {
+    pub sensors_present: u32,
+    pub sensors_enabled: u32,
+    pub sensors_health: u32,
+    pub load: u16, // 0.1 %
+    pub voltage_battery: u16, // mV, u16::MAX when unknown
+    pub current_battery: i16, // cA, -1 when unknown
+    pub drop_rate_comm: u16,
+    pub errors_comm: u16,
+    pub errors_count: [u16; 4],
+    pub battery_remaining: i8, // %, -1 when unknown
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Attitude {
+    pub time_boot_ms: u32,
+    pub roll: f32, // rad
+    pub pitch: f32,
+    pub yaw: f32,
+    pub rollspeed: f32, // rad/s
+    pub pitchspeed: f32,
+    pub yawspeed: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BatteryStatus {
+    pub current_consumed: i32, // mAh, -1 when unknown
+    pub energy_consumed: i32, // hJ, -1 when unknown
+    pub temperature: i16, // cdegC, i16::MAX when unknown
+    pub voltages: [u16; 10], // mV per cell, u16::MAX for unused cells
+    pub current_battery: i16, // cA, -1 when unknown
+    pub id: u8,
+    pub battery_function: u8,
+    pub battery_type: u8,
+    pub battery_remaining: i8, // %, -1 when unknown
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EscStatus {
+    pub time_usec: u64,
+    pub rpm: [i32; 4],
+    pub voltage: [f32; 4],
+    pub current: [f32; 4],
+    pub index: u8, // first ESC in this message
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CommandLong {
+    pub params: [f32; 7],
+    pub command: u16,
+    pub target_system: u8,
+    pub target_component: u8,
+    pub confirmation: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CommandAck {
+    pub command: u16,
+    pub result: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Message {
+    Heartbeat(Heartbeat),
+    SysStatus(SysStatus),
+    Attitude(Attitude),
+    CommandLong(CommandLong),
+    CommandAck(CommandAck),
+    BatteryStatus(BatteryStatus),
+    EscStatus(EscStatus),
+}
+
+/// Payload length and CRC_EXTRA of a known message id.
+fn info(id: u32) -> Option<(usize, u8)> {
+    let ret = match id {
+        0 => (9, 50),
+        1 => (31, 124),
+        30 => (28, 39),
+        76 => (33, 152),
+        77 => (3, 143),
+        147 => (36, 154),
+        291 => (57, 10),
+        _ => return None,
+    };
+    Some(ret)
+}
+
+impl Message {
+    pub fn id(&self) -> u32 {
+        match self {
+            Self::Heartbeat(_) => 0,
+            Self::SysStatus(_) => 1,
+            Self::Attitude(_) => 30,
+            Self::CommandLong(_) => 76,
+            Self::CommandAck(_) => 77,
+            Self::BatteryStatus(_) => 147,
+            Self::EscStatus(_) => 291,
+        }
+    }
+
+    /// Writes the full, untruncated payload.
+    fn serialize(&self, buf: &mut [u8; MAX_PAYLOAD]) -> usize {
+        let mut w = Writer { buf, len: 0 };
+        match self {
+            Self::Heartbeat(m) => {
+                w.u32(m.custom_mode).u8(m.mav_type).u8(m.autopilot).u8(m.base_mode);
+                w.u8(m.system_status).u8(m.mavlink_version);
+            }
+            Self::SysStatus(m) => {
+                w.u32(m.sensors_present).u32(m.sensors_enabled).u32(m.sensors_health);
+                w.u16(m.load).u16(m.voltage_battery).i16(m.current_battery);
+                w.u16(m.drop_rate_comm).u16(m.errors_comm);
+                m.errors_count.iter().for_each(|v| { w.u16(*v); });
+                w.u8(m.battery_remaining as u8);
+            }
+            Self::Attitude(m) => {
+                w.u32(m.time_boot_ms).f32(m.roll).f32(m.pitch).f32(m.yaw);
+                w.f32(m.rollspeed).f32(m.pitchspeed).f32(m.yawspeed);
+            }
+            Self::CommandLong(m) => {
+                m.params.iter().for_each(|v| { w.f32(*v); });
+                w.u16(m.command).u8(m.target_system).u8(m.target_component).u8(m.confirmation);
+            }
+            Self::CommandAck(m) => { w.u16(m.command).u8(m.result); }
+            Self::BatteryStatus(m) => {
+                w.i32(m.current_consumed).i32(m.energy_consumed).i16(m.temperature);
+                m.voltages.iter().for_each(|v| { w.u16(*v); });
+                w.i16(m.current_battery).u8(m.id).u8(m.battery_function).u8(m.battery_type);
+                w.u8(m.battery_remaining as u8);
+            }
+            Self::EscStatus(m) => {
+                w.u64(m.time_usec);
+                m.rpm.iter().for_each(|v| { w.i32(*v); });
+                m.voltage.iter().for_each(|v| { w.f32(*v); });
+                m.current.iter().for_each(|v| { w.f32(*v); });
+                w.u8(m.index);
+            }
+        }
+        w.len
+    }
+
+    fn deserialize(id: u32, payload: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(payload);
+        let ret = match id {
+            0 => Self::Heartbeat(Heartbeat {
+                custom_mode: r.u32(),
+                mav_type: r.u8(),
+                autopilot: r.u8(),
+                base_mode: r.u8(),
+                system_status: r.u8(),
+                mavlink_version: r.u8(),
+            }),
+            1 => Self::SysStatus(SysStatus {
+                sensors_present: r.u32(),
+                sensors_enabled: r.u32(),
+                sensors_health: r.u32(),
+                load: r.u16(),
+                voltage_battery: r.u16(),
+                current_battery: r.i16(),
+                drop_rate_comm: r.u16(),
+                errors_comm: r.u16(),
+                errors_count: [r.u16(), r.u16(), r.u16(), r.u16()],
+                battery_remaining: r.i8(),
+            }),
+            30 => Self::Attitude(Attitude {
+                time_boot_ms: r.u32(),
+                roll: r.f32(),
+                pitch: r.f32(),
+                yaw: r.f32(),
+                rollspeed: r.f32(),
+                pitchspeed: r.f32(),
+                yawspeed: r.f32(),
+            }),
+            76 => Self::CommandLong(CommandLong {
+                params: core::array::from_fn(|_| r.f32()),
+                command: r.u16(),
+                target_system: r.u8(),
+                target_component: r.u8(),
+                confirmation: r.u8(),
+            }),
+            77 => Self::CommandAck(CommandAck { command: r.u16(), result: r.u8() }),
+            147 => Self::BatteryStatus(BatteryStatus {
+                current_consumed: r.i32(),
+                energy_consumed: r.i32(),
+                temperature: r.i16(),
+                voltages: core::array::from_fn(|_| r.u16()),
+                current_battery: r.i16(),
+                id: r.u8(),
+                battery_function: r.u8(),
+                battery_type: r.u8(),
+                battery_remaining: r.i8(),
+            }),
+            291 => Self::EscStatus(EscStatus {
+                time_usec: r.u64(),
+                rpm: core::array::from_fn(|_| r.i32()),
+                voltage: core::array::from_fn(|_| r.f32()),
+                current: core::array::from_fn(|_| r.f32()),
+                index: r.u8(),
+            }),
+            _ => return None,
+        };
+        Some(ret)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    pub seq: u8,
+    pub system_id: u8,
+    pub component_id: u8,
+    pub message: Message,
+}
+
+impl Frame {
+    /// Writes an unsigned v2 frame with trailing zeros truncated, returns its length.
+    pub fn encode(&self, out: &mut [u8]) -> Result<usize, MavError> {
+        let mut payload = [0u8; MAX_PAYLOAD];
+        let len = self.message.serialize(&mut payload);
+        let len = payload[..len].iter().rposition(|b| *b != 0).map_or(1, |idx| idx + 1);
+
+        let frame_len = HEADER_LEN + len + 2;
+        if out.len() < frame_len {
+            return Err(MavError::Overflow);
+        }
+        let id = self.message.id().to_le_bytes();
+        out[..HEADER_LEN].copy_from_slice(&[
+            STX, len as u8, 0, 0, self.seq, self.system_id, self.component_id, id[0], id[1], id[2],
+        ]);
+        out[HEADER_LEN..HEADER_LEN + len].copy_from_slice(&payload[..len]);
+        let (_, extra) = info(self.message.id()).ok_or(MavError::Unknown(self.message.id()))?;
+        let crc = crc16_mcrf4xx_update(crc16_mcrf4xx_update(0xFFFF, &out[1..HEADER_LEN + len]), &[extra]);
+        out[HEADER_LEN + len..frame_len].copy_from_slice(&crc.to_le_bytes());
+        Ok(frame_len)
+    }
+
+    /// Decodes one complete frame starting at the magic byte.
+    pub fn decode(frame: &[u8]) -> Result<Self, MavError> {
+        if frame.len() < HEADER_LEN + 2 || frame[0] != STX {
+            return Err(MavError::Framing);
+        }
+        let len = frame[1] as usize;
+        if frame.len() < HEADER_LEN + len + 2 {
+            return Err(MavError::Framing);
+        }
+        let id = u32::from_le_bytes([frame[7], frame[8], frame[9], 0]);
+        let (max_len, extra) = info(id).ok_or(MavError::Unknown(id))?;
+        let crc = crc16_mcrf4xx_update(crc16_mcrf4xx_update(0xFFFF, &frame[1..HEADER_LEN + len]), &[extra]);
+        let expected = u16::from_le_bytes([frame[HEADER_LEN + len], frame[HEADER_LEN + len + 1]]);
+        if crc != expected {
+            return Err(MavError::Checksum);
+        }
+        // extension fields beyond the base message are ignored
+        let payload = &frame[HEADER_LEN..HEADER_LEN + len.min(max_len)];
+        let message = Message::deserialize(id, payload).ok_or(MavError::Unknown(id))?;
+        Ok(Self { seq: frame[4], system_id: frame[5], component_id: frame[6], message })
+    }
+}
+
+/// Byte at a time frame parser, skips v1 traffic and anything until the next magic byte.
+pub struct Parser {
+    buf: [u8; HEADER_LEN + MAX_PAYLOAD + 2 + SIGNATURE_LEN],
+    len: usize,
+}
+
+impl Default for Parser {
+    fn default() -> Self { Self::new() }
+}
+
+impl Parser {
+    pub const fn new() -> Self { Self { buf: [0; HEADER_LEN + MAX_PAYLOAD + 2 + SIGNATURE_LEN], len: 0 } }
+
+    fn expected(&self) -> Option<usize> {
+        if self.len < 3 {
+            return None;
+        }
+        let signature = if self.buf[2] & FLAG_SIGNED != 0 { SIGNATURE_LEN } else { 0 };
+        Some(HEADER_LEN + self.buf[1] as usize + 2 + signature)
+    }
+
+    pub fn push(&mut self, b: u8) -> Option<Result<Frame, MavError>> {
+        if self.len == 0 && b != STX {
+            return None;
+        }
+        self.buf[self.len] = b;
+        self.len += 1;
+        match self.expected() {
+            Some(expected) if self.len == expected => {
+                self.len = 0;
+                Some(Frame::decode(&self.buf[..expected]))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ArduPilot heartbeat, quadrotor in stabilize
+    const HEARTBEAT: [u8; 21] = [
+        0xfd, 0x09, 0x00, 0x00, 0xef, 0x01, 0x01, 0x00, 0x00, 0x00,
+        0x05, 0x00, 0x00, 0x00, 0x02, 0x03, 0x59, 0x03, 0x03,
+        0x10, 0xf0,
+    ];
+
+    // hand assembled, payload truncated after current_battery
+    const SYS_STATUS: [u8; 30] = [
+        0xfd, 0x12, 0x00, 0x00, 0x10, 0x01, 0x01, 0x01, 0x00, 0x00,
+        0x2f, 0x00, 0x00, 0x00, 0x2f, 0x00, 0x00, 0x00, 0x2f, 0x00, 0x00, 0x00,
+        0xfa, 0x00, 0x76, 0x2f, 0x7b, 0x00,
+        0x7e, 0x1d,
+    ];
+
+    fn heartbeat() -> Frame {
+        Frame {
+            seq: 0xef,
+            system_id: 1,
+            component_id: 1,
+            message: Message::Heartbeat(Heartbeat {
+                custom_mode: 5,
+                mav_type: mav_type::QUADROTOR,
+                autopilot: 3,
+                base_mode: 0x59,
+                system_status: 3,
+                mavlink_version: 3,
+            }),
+        }
+    }
+
+    #[test]
+    fn decode_heartbeat() {
+        assert_eq!(Frame::decode(&HEARTBEAT), Ok(heartbeat()));
+    }
+
+    #[test]
+    fn decode_sys_status() {
+        let frame = Frame::decode(&SYS_STATUS).unwrap();
+        assert_eq!((frame.seq, frame.system_id, frame.component_id), (0x10, 1, 1));
+        let expected = SysStatus {
+            sensors_present: 0x2f,
+            sensors_enabled: 0x2f,
+            sensors_health: 0x2f,
+            load: 250,
+            voltage_battery: 12150,
+            current_battery: 123,
+            ..Default::default()
+        };
+        assert_eq!(frame.message, Message::SysStatus(expected));
+    }
+
+    #[test]
+    fn encode_matches_capture() {
+        let mut out = [0u8; MAX_FRAME];
+        let len = heartbeat().encode(&mut out).unwrap();
+        assert_eq!(&out[..len], &HEARTBEAT);
+    }
+
+    #[test]
+    fn encode_truncates_zeros() {
+        let mut out = [0u8; MAX_FRAME];
+        let ack = CommandAck { command: command::COMPONENT_ARM_DISARM, result: result::ACCEPTED };
+        let frame = Frame { seq: 0, system_id: 1, component_id: 1, message: Message::CommandAck(ack) };
+        let len = frame.encode(&mut out).unwrap();
+        assert_eq!((len, out[1]), (HEADER_LEN + 2 + 2, 2));
+        assert_eq!(Frame::decode(&out[..len]), Ok(frame));
+
+        // an all zero payload keeps its first byte
+        let frame = Frame { message: Message::Heartbeat(Heartbeat::default()), ..frame };
+        let len = frame.encode(&mut out).unwrap();
+        assert_eq!((len, out[1]), (HEADER_LEN + 1 + 2, 1));
+        assert_eq!(Frame::decode(&out[..len]), Ok(frame));
+    }
+
+    /// CRC_EXTRA from the message definition, fields in wire order without extensions.
+    fn crc_extra(name: &str, fields: &[(&str, &str, u8)]) -> u8 {
+        let mut crc = crc16_mcrf4xx_update(0xFFFF, name.as_bytes());
+        crc = crc16_mcrf4xx_update(crc, b" ");
+        for (ty, field, array) in fields {
+            crc = crc16_mcrf4xx_update(crc, ty.as_bytes());
+            crc = crc16_mcrf4xx_update(crc, b" ");
+            crc = crc16_mcrf4xx_update(crc, field.as_bytes());
+            crc = crc16_mcrf4xx_update(crc, b" ");
+            if *array > 0 {
+                crc = crc16_mcrf4xx_update(crc, &[*array]);
+            }
+        }
+        (crc & 0xFF) as u8 ^ (crc >> 8) as u8
+    }
+
+    #[test]
+    fn crc_extra_matches_definitions() {
+        let heartbeat = [
+            ("uint32_t", "custom_mode", 0),
+            ("uint8_t", "type", 0),
+            ("uint8_t", "autopilot", 0),
+            ("uint8_t", "base_mode", 0),
+            ("uint8_t", "system_status", 0),
+            ("uint8_t", "mavlink_version", 0),
+        ];
+        assert_eq!(info(0).unwrap().1, crc_extra("HEARTBEAT", &heartbeat));
+
+        let sys_status = [
+            ("uint32_t", "onboard_control_sensors_present", 0),
+            ("uint32_t", "onboard_control_sensors_enabled", 0),
+            ("uint32_t", "onboard_control_sensors_health", 0),
+            ("uint16_t", "load", 0),
+            ("uint16_t", "voltage_battery", 0),
+            ("int16_t", "current_battery", 0),
+            ("uint16_t", "drop_rate_comm", 0),
+            ("uint16_t", "errors_comm", 0),
+            ("uint16_t", "errors_count1", 0),
+            ("uint16_t", "errors_count2", 0),
+            ("uint16_t", "errors_count3", 0),
+            ("uint16_t", "errors_count4", 0),
+            ("int8_t", "battery_remaining", 0),
+        ];
+        assert_eq!(info(1).unwrap().1, crc_extra("SYS_STATUS", &sys_status));
+
+        let attitude = [
+            ("uint32_t", "time_boot_ms", 0),
+            ("float", "roll", 0),
+            ("float", "pitch", 0),
+            ("float", "yaw", 0),
+            ("float", "rollspeed", 0),
+            ("float", "pitchspeed", 0),
+            ("float", "yawspeed", 0),
+        ];
+        assert_eq!(info(30).unwrap().1, crc_extra("ATTITUDE", &attitude));
+
+        let command_long = [
+            ("float", "param1", 0),
+            ("float", "param2", 0),
+            ("float", "param3", 0),
+            ("float", "param4", 0),
+            ("float", "param5", 0),
+            ("float", "param6", 0),
+            ("float", "param7", 0),
+            ("uint16_t", "command", 0),
+            ("uint8_t", "target_system", 0),
+            ("uint8_t", "target_component", 0),
+            ("uint8_t", "confirmation", 0),
+        ];
+        assert_eq!(info(76).unwrap().1, crc_extra("COMMAND_LONG", &command_long));
+
+        let command_ack = [("uint16_t", "command", 0), ("uint8_t", "result", 0)];
+        assert_eq!(info(77).unwrap().1, crc_extra("COMMAND_ACK", &command_ack));
+
+        let battery_status = [
+            ("int32_t", "current_consumed", 0),
+            ("int32_t", "energy_consumed", 0),
+            ("int16_t", "temperature", 0),
+            ("uint16_t", "voltages", 10),
+            ("int16_t", "current_battery", 0),
+            ("uint8_t", "id", 0),
+            ("uint8_t", "battery_function", 0),
+            ("uint8_t", "type", 0),
+            ("int8_t", "battery_remaining", 0),
+        ];
+        assert_eq!(info(147).unwrap().1, crc_extra("BATTERY_STATUS", &battery_status));
+
+        let esc_status = [
+            ("uint64_t", "time_usec", 0),
+            ("int32_t", "rpm", 4),
+            ("float", "voltage", 4),
+            ("float", "current", 4),
+            ("uint8_t", "index", 0),
+        ];
+        assert_eq!(info(291).unwrap().1, crc_extra("ESC_STATUS", &esc_status));
+    }
+
+    #[test]
+    fn rejects_corruption() {
+        let mut frame = HEARTBEAT;
+        frame[12] ^= 0x01;
+        assert_eq!(Frame::decode(&frame), Err(MavError::Checksum));
+
+        let mut frame = HEARTBEAT;
+        frame[20] ^= 0x01;
+        assert_eq!(Frame::decode(&frame), Err(MavError::Checksum));
+
+        assert_eq!(Frame::decode(&HEARTBEAT[..15]), Err(MavError::Framing));
+        assert_eq!(Frame::decode(&HEARTBEAT[1..]), Err(MavError::Framing));
+    }
+
+    #[test]
+    fn parser_resyncs() {
+        let mut parser = Parser::new();
+        let mut frames = 0;
+        let garbage = [0x00, 0x55, 0xfe, 0x09];
+        for b in garbage.iter().chain(&HEARTBEAT).chain(&SYS_STATUS) {
+            if let Some(frame) = parser.push(*b) {
+                frame.unwrap();
+                frames += 1;
+            }
+        }
+        assert_eq!(frames, 2);
+    }
+}
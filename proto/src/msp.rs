@@ -0,0 +1,408 @@
+//! MultiWii Serial Protocol, v1 `$M` and v2 `$X` framing with a small read only command set.
+
+use crate::crc::crc8_dvb_s2_update;
+
+pub const MAX_PAYLOAD: usize = 64;
+pub const MOTORS: usize = 8;
+pub const RC_CHANNELS: usize = 16;
+
+pub mod cmd {
+    pub const API_VERSION: u16 = 1;
+    pub const FC_VARIANT: u16 = 2;
+    pub const STATUS: u16 = 101;
+    pub const MOTOR: u16 = 104;
+    pub const RC: u16 = 105;
+    pub const ATTITUDE: u16 = 108;
+    pub const ANALOG: u16 = 110;
+    pub const BATTERY_STATE: u16 = 130;
+    pub const SET_MOTOR: u16 = 214;
+}
+
+const API_VERSION: [u8; 3] = [0, 1, 46];
+const FC_VARIANT: &[u8; 4] = b"PNGN";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Version {
+    V1,
+    V2,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MspError {
+    Checksum,
+    Overflow, // payload larger than `MAX_PAYLOAD` or the output buffer
+    Payload, // request payload too short for the command
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub version: Version,
+    pub cmd: u16,
+    len: usize,
+    payload: [u8; MAX_PAYLOAD],
+}
+
+impl Request {
+    pub fn new(version: Version, cmd: u16, payload: &[u8]) -> Result<Self, MspError> {
+        let mut ret = Self { version, cmd, len: payload.len(), payload: [0; MAX_PAYLOAD] };
+        ret.payload.get_mut(..payload.len()).ok_or(MspError::Overflow)?.copy_from_slice(payload);
+        Ok(ret)
+    }
+
+    pub fn payload(&self) -> &[u8] { &self.payload[..self.len] }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+    Magic,
+    Direction(Version),
+    V1Size,
+    V1Cmd,
+    V2Flag,
+    V2Cmd(u8), // bytes read
+    V2Size(u8),
+    Payload,
+    Checksum,
+}
+
+/// Byte at a time request parser, only accepts `<` frames.
+pub struct Parser {
+    state: State,
+    version: Version,
+    cmd: u16,
+    size: usize,
+    len: usize,
+    crc: u8,
+    payload: [u8; MAX_PAYLOAD],
+}
+
+impl Default for Parser {
+    fn default() -> Self { Self::new() }
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Self { state: State::Idle, version: Version::V1, cmd: 0, size: 0, len: 0, crc: 0, payload: [0; MAX_PAYLOAD] }
+    }
+
+    fn checksum(&mut self, b: u8) {
+        self.crc = match self.version {
+            Version::V1 => self.crc ^ b,
+            Version::V2 => crc8_dvb_s2_update(self.crc, &[b]),
+        };
+    }
+
+    fn after_size(&mut self) -> Option<Result<Request, MspError>> {
+        if self.size > MAX_PAYLOAD {
+            self.state = State::Idle;
+            return Some(Err(MspError::Overflow));
+        }
+        self.state = if self.size == 0 { State::Checksum } else { State::Payload };
+        None
+    }
+
+    pub fn push(&mut self, b: u8) -> Option<Result<Request, MspError>> {
+        match self.state {
+            State::Idle => if b == b'$' { self.state = State::Magic },
+            State::Magic => self.state = match b {
+                b'M' => State::Direction(Version::V1),
+                b'X' => State::Direction(Version::V2),
+                b'$' => State::Magic,
+                _ => State::Idle,
+            },
+            State::Direction(version) => {
+                self.version = version;
+                self.crc = 0;
+                self.len = 0;
+                self.state = match (b, version) {
+                    (b'<', Version::V1) => State::V1Size,
+                    (b'<', Version::V2) => State::V2Flag,
+                    (b'$', _) => State::Magic,
+                    _ => State::Idle,
+                };
+            }
+            State::V1Size => {
+                self.checksum(b);
+                self.size = b as usize;
+                self.state = State::V1Cmd;
+            }
+            State::V1Cmd => {
+                self.checksum(b);
+                self.cmd = b as u16;
+                return self.after_size();
+            }
+            State::V2Flag => {
+                self.checksum(b);
+                self.cmd = 0;
+                self.size = 0;
+                self.state = State::V2Cmd(0);
+            }
+            State::V2Cmd(n) => {
+                self.checksum(b);
+                self.cmd |= (b as u16) << (8 * n);
+                self.state = if n == 0 { State::V2Cmd(1) } else { State::V2Size(0) };
+            }
+            State::V2Size(n) => {
+                self.checksum(b);
+                self.size |= (b as usize) << (8 * n);
+                if n == 0 {
+                    self.state = State::V2Size(1);
+                } else {
+                    return self.after_size();
+                }
+            }
+            State::Payload => {
+                self.checksum(b);
+                self.payload[self.len] = b;
+                self.len += 1;
+                if self.len == self.size {
+                    self.state = State::Checksum;
+                }
+            }
+            State::Checksum => {
+                self.state = State::Idle;
+                if b != self.crc {
+                    return Some(Err(MspError::Checksum));
+                }
+                return Some(Ok(Request {
+                    version: self.version,
+                    cmd: self.cmd,
+                    len: self.len,
+                    payload: self.payload,
+                }));
+            }
+        }
+        None
+    }
+}
+
+/// Writes a `>` response, or `!` on error, returns the frame length.
+pub fn encode(version: Version, cmd: u16, payload: &[u8], error: bool, out: &mut [u8]) -> Result<usize, MspError> {
+    let direction = if error { b'!' } else { b'>' };
+    let header = match version {
+        Version::V1 => 5,
+        Version::V2 => 8,
+    };
+    let len = header + payload.len() + 1;
+    if payload.len() > MAX_PAYLOAD || out.len() < len {
+        return Err(MspError::Overflow);
+    }
+
+    let crc = match version {
+        Version::V1 => {
+            out[..5].copy_from_slice(&[b'$', b'M', direction, payload.len() as u8, cmd as u8]);
+            out[3..5].iter().chain(payload).fold(0, |crc, b| crc ^ b)
+        }
+        Version::V2 => {
+            let [cmd_lo, cmd_hi] = cmd.to_le_bytes();
+            let [size_lo, size_hi] = (payload.len() as u16).to_le_bytes();
+            out[..8].copy_from_slice(&[b'$', b'X', direction, 0, cmd_lo, cmd_hi, size_lo, size_hi]);
+            crc8_dvb_s2_update(crc8_dvb_s2_update(0, &out[3..8]), payload)
+        }
+    };
+    out[header..header + payload.len()].copy_from_slice(payload);
+    out[len - 1] = crc;
+    Ok(len)
+}
+
+/// Little endian payload builder.
+pub struct Writer<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Self { Self { buf, len: 0 } }
+
+    pub fn len(&self) -> usize { self.len }
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    pub fn bytes(&mut self, b: &[u8]) -> Result<&mut Self, MspError> {
+        let end = self.len + b.len();
+        self.buf.get_mut(self.len..end).ok_or(MspError::Overflow)?.copy_from_slice(b);
+        self.len = end;
+        Ok(self)
+    }
+
+    pub fn u8(&mut self, v: u8) -> Result<&mut Self, MspError> { self.bytes(&[v]) }
+    pub fn u16(&mut self, v: u16) -> Result<&mut Self, MspError> { self.bytes(&v.to_le_bytes()) }
+    pub fn i16(&mut self, v: i16) -> Result<&mut Self, MspError> { self.bytes(&v.to_le_bytes()) }
+    pub fn u32(&mut self, v: u32) -> Result<&mut Self, MspError> { self.bytes(&v.to_le_bytes()) }
+}
+
+/// Flight state served to MSP clients, in MSP units.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Snapshot {
+    pub cycle_time_us: u16,
+    pub armed: bool,
+    pub roll: i16, // 0.1 degree
+    pub pitch: i16, // 0.1 degree
+    pub yaw: i16, // degree
+    pub motors: [u16; MOTORS], // 1000..=2000
+    pub rc: [u16; RC_CHANNELS], // 1000..=2000
+    pub rc_count: u8,
+    pub cells: u8,
+    pub capacity_mah: u16,
+    pub voltage_cv: u16, // 0.01 V
+    pub current_ca: i16, // 0.01 A
+    pub consumed_mah: u16,
+    pub rssi: u16, // 0..=1023
+    pub battery_state: u8, // 0 ok, 1 warning, 2 critical, 3 not present
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    SetMotor([u16; MOTORS]),
+}
+
+fn payload(snapshot: &Snapshot, request: &Request, w: &mut Writer) -> Result<Option<Action>, MspError> {
+    let s = snapshot;
+    match request.cmd {
+        cmd::API_VERSION => { w.bytes(&API_VERSION)?; }
+        cmd::FC_VARIANT => { w.bytes(FC_VARIANT)?; }
+        cmd::STATUS => {
+            w.u16(s.cycle_time_us)?.u16(0)?.u16(0)?.u32(s.armed as u32)?.u8(0)?;
+        }
+        cmd::ATTITUDE => { w.i16(s.roll)?.i16(s.pitch)?.i16(s.yaw)?; }
+        cmd::MOTOR => {
+            for m in s.motors {
+                w.u16(m)?;
+            }
+        }
+        cmd::RC => {
+            for ch in &s.rc[..(s.rc_count as usize).min(RC_CHANNELS)] {
+                w.u16(*ch)?;
+            }
+        }
+        cmd::ANALOG => {
+            let vbat = (s.voltage_cv / 10).min(255) as u8;
+            w.u8(vbat)?.u16(s.consumed_mah)?.u16(s.rssi)?.i16(s.current_ca)?.u16(s.voltage_cv)?;
+        }
+        cmd::BATTERY_STATE => {
+            let vbat = (s.voltage_cv / 10).min(255) as u8;
+            w.u8(s.cells)?.u16(s.capacity_mah)?.u8(vbat)?.u16(s.consumed_mah)?;
+            w.i16(s.current_ca)?.u8(s.battery_state)?.u16(s.voltage_cv)?;
+        }
+        cmd::SET_MOTOR => {
+            let p = request.payload();
+            if p.len() < 2 * MOTORS {
+                return Err(MspError::Payload);
+            }
+            let mut motors = [0; MOTORS];
+            for (idx, m) in motors.iter_mut().enumerate() {
+                *m = u16::from_le_bytes([p[2 * idx], p[2 * idx + 1]]);
+            }
+            return Ok(Some(Action::SetMotor(motors)));
+        }
+        _ => return Err(MspError::Payload),
+    }
+    Ok(None)
+}
+
+/// Answers `request` into `out`, unknown or malformed requests get an error response.
+pub fn respond(snapshot: &Snapshot, request: &Request, out: &mut [u8]) -> Result<(usize, Option<Action>), MspError> {
+    let mut buf = [0u8; MAX_PAYLOAD];
+    let mut w = Writer::new(&mut buf);
+    let (error, action) = match payload(snapshot, request, &mut w) {
+        Ok(action) => (false, action),
+        Err(MspError::Payload) => (true, None),
+        Err(err) => return Err(err),
+    };
+    let len = if error { 0 } else { w.len() };
+    let ret = encode(request.version, request.cmd, &buf[..len], error, out)?;
+    Ok((ret, action))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V1_API_VERSION: [u8; 6] = [b'$', b'M', b'<', 0x00, 0x01, 0x01];
+    const IDENT: u16 = 100; // MSP_IDENT, not served
+    const V2_IDENT: [u8; 9] = [b'$', b'X', b'<', 0x00, 0x64, 0x00, 0x00, 0x00, 0x8f];
+
+    fn parse(bytes: &[u8]) -> ([Option<Result<Request, MspError>>; 4], usize) {
+        let mut parser = Parser::new();
+        let mut ret = [None, None, None, None];
+        let mut len = 0;
+        for b in bytes {
+            if let Some(result) = parser.push(*b) {
+                ret[len] = Some(result);
+                len += 1;
+            }
+        }
+        (ret, len)
+    }
+
+    fn parse_one(bytes: &[u8]) -> Result<Request, MspError> {
+        let (ret, len) = parse(bytes);
+        assert_eq!(len, 1);
+        ret[0].clone().unwrap()
+    }
+
+    #[test]
+    fn v1() {
+        assert_eq!(parse_one(&V1_API_VERSION), Request::new(Version::V1, cmd::API_VERSION, &[]));
+        let mut frame = [0u8; 16];
+        let len = encode(Version::V1, cmd::SET_MOTOR, &[1, 2, 3], false, &mut frame).unwrap();
+        frame[2] = b'<';
+        assert_eq!(parse_one(&frame[..len]), Request::new(Version::V1, cmd::SET_MOTOR, &[1, 2, 3]));
+    }
+
+    #[test]
+    fn v2() {
+        assert_eq!(parse_one(&V2_IDENT), Request::new(Version::V2, IDENT, &[]));
+        let mut frame = [0u8; 16];
+        let len = encode(Version::V2, 0x1234, &[9, 8], false, &mut frame).unwrap();
+        frame[2] = b'<';
+        assert_eq!(parse_one(&frame[..len]), Request::new(Version::V2, 0x1234, &[9, 8]));
+    }
+
+    #[test]
+    fn checksum() {
+        let mut frame = V1_API_VERSION;
+        frame[5] ^= 0x01;
+        assert_eq!(parse_one(&frame), Err(MspError::Checksum));
+
+        // a v1 xor checksum would pass here, CRC8 DVB-S2 does not
+        let mut frame = V2_IDENT;
+        frame[4] ^= 0x01;
+        frame[8] ^= 0x01;
+        assert_eq!(parse_one(&frame), Err(MspError::Checksum));
+    }
+
+    #[test]
+    fn oversize() {
+        // 255 announces a v1 jumbo frame, larger than we accept
+        let jumbo = [b'$', b'M', b'<', 0xff, 0x01];
+        assert_eq!(parse_one(&jumbo), Err(MspError::Overflow));
+        let v1 = [b'$', b'M', b'<', MAX_PAYLOAD as u8 + 1, 0x01];
+        assert_eq!(parse_one(&v1), Err(MspError::Overflow));
+        let v2 = [b'$', b'X', b'<', 0x00, 0x64, 0x00, 0x00, 0x01];
+        assert_eq!(parse_one(&v2), Err(MspError::Overflow));
+
+        let mut frame = [0u8; MAX_PAYLOAD + 16];
+        let payload = [0x55; MAX_PAYLOAD];
+        let len = encode(Version::V2, cmd::STATUS, &payload, false, &mut frame).unwrap();
+        frame[2] = b'<';
+        assert_eq!(parse_one(&frame[..len]), Request::new(Version::V2, cmd::STATUS, &payload));
+        assert_eq!(encode(Version::V2, cmd::STATUS, &[0; MAX_PAYLOAD + 1], false, &mut frame), Err(MspError::Overflow));
+    }
+
+    #[test]
+    fn resync() {
+        let mut bytes = [0u8; 64];
+        let mut len = 0;
+        for chunk in [&b"\x00$M>$$"[..], &V1_API_VERSION, b"$X$", &V2_IDENT, b"$M<\xff\x01", &V1_API_VERSION] {
+            bytes[len..len + chunk.len()].copy_from_slice(chunk);
+            len += chunk.len();
+        }
+        let (ret, count) = parse(&bytes[..len]);
+        assert_eq!(count, 4);
+        assert_eq!(ret[0], Some(Request::new(Version::V1, cmd::API_VERSION, &[])));
+        assert_eq!(ret[1], Some(Request::new(Version::V2, IDENT, &[])));
+        assert_eq!(ret[2], Some(Err(MspError::Overflow)));
+        assert_eq!(ret[3], Some(Request::new(Version::V1, cmd::API_VERSION, &[])));
+    }
+}
@@ -0,0 +1,108 @@
+//! PPM sum frames, rebuilt from rising edge to rising edge periods.
+
+use crate::rc::RcChannels;
+
+pub const MAX_CHANNELS: usize = 12;
+
+#[derive(Debug, Clone)]
+pub struct PpmConfig {
+    pub sync_us: u32, // shortest period taken as the frame gap
+    pub min_us: u32, // channel periods outside these bounds drop the frame
+    pub max_us: u32,
+    pub min_channels: usize,
+}
+
+impl Default for PpmConfig {
+    fn default() -> Self {
+        Self { sync_us: 2700, min_us: 750, max_us: 2250, min_channels: 4 }
+    }
+}
+
+pub struct PpmDecoder {
+    config: PpmConfig,
+    channels: RcChannels,
+    synced: bool,
+}
+
+impl PpmDecoder {
+    pub fn new(config: PpmConfig) -> Self {
+        Self { config, channels: RcChannels::default(), synced: false }
+    }
+
+    pub fn set_config(&mut self, config: PpmConfig) { self.config = config; }
+
+    /// Feeds one period, returns a frame on the sync gap that ends it.
+    pub fn push(&mut self, period_us: u32) -> Option<RcChannels> {
+        if period_us >= self.config.sync_us {
+            let count = self.channels.count as usize;
+            let ret = (self.synced && count >= self.config.min_channels).then_some(self.channels);
+            self.channels.count = 0;
+            self.synced = true;
+            return ret;
+        }
+        let count = self.channels.count as usize;
+        if !(self.config.min_us..=self.config.max_us).contains(&period_us) || count >= MAX_CHANNELS {
+            self.synced = false; // glitch, wait for the next gap
+            return None;
+        }
+        self.channels.us[count] = period_us as u16;
+        self.channels.count += 1;
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYNC: u32 = 5000;
+
+    fn feed(decoder: &mut PpmDecoder, periods: &[u32]) -> Option<RcChannels> {
+        let mut ret = None;
+        for &period in periods {
+            if let Some(channels) = decoder.push(period) {
+                assert!(ret.is_none(), "more than one frame");
+                ret = Some(channels);
+            }
+        }
+        ret
+    }
+
+    #[test]
+    fn frames_after_sync() {
+        let mut decoder = PpmDecoder::new(PpmConfig::default());
+        // partial frame before the first gap is dropped
+        assert_eq!(feed(&mut decoder, &[1500, 1600, SYNC]), None);
+        let channels = feed(&mut decoder, &[1000, 1500, 2000, 1200, 1800, SYNC]).unwrap();
+        assert_eq!(channels.as_slice(), &[1000, 1500, 2000, 1200, 1800]);
+        let channels = feed(&mut decoder, &[1100, 1200, 1300, 1400, 2700]).unwrap();
+        assert_eq!(channels.as_slice(), &[1100, 1200, 1300, 1400]);
+    }
+
+    #[test]
+    fn glitch_desyncs() {
+        let mut decoder = PpmDecoder::new(PpmConfig::default());
+        feed(&mut decoder, &[SYNC]);
+        assert_eq!(feed(&mut decoder, &[1500, 300, 1500, 1500, 1500, SYNC]), None);
+        assert_eq!(feed(&mut decoder, &[1500, 1500, 2600, 1500, 1500, SYNC]), None);
+        assert!(feed(&mut decoder, &[1500, 1500, 1500, 1500, SYNC]).is_some());
+    }
+
+    #[test]
+    fn min_channels() {
+        let mut decoder = PpmDecoder::new(PpmConfig { min_channels: 6, ..Default::default() });
+        feed(&mut decoder, &[SYNC]);
+        assert_eq!(feed(&mut decoder, &[1500; 5]).or(decoder.push(SYNC)), None);
+        assert_eq!(feed(&mut decoder, &[1500; 6]).or(decoder.push(SYNC)).map(|c| c.count), Some(6));
+    }
+
+    #[test]
+    fn channel_overflow() {
+        let mut decoder = PpmDecoder::new(PpmConfig::default());
+        feed(&mut decoder, &[SYNC]);
+        let full = feed(&mut decoder, &[1500; MAX_CHANNELS]).or(decoder.push(SYNC));
+        assert_eq!(full.map(|c| c.count as usize), Some(MAX_CHANNELS));
+        assert_eq!(feed(&mut decoder, &[1500; MAX_CHANNELS + 1]).or(decoder.push(SYNC)), None);
+        assert!(feed(&mut decoder, &[1500; 8]).or(decoder.push(SYNC)).is_some());
+    }
+}
@@ -0,0 +1,440 @@
+//! Receiver channel values shared by all RC protocols.
+
+pub const MAX_CHANNELS: usize = 18;
+pub const MIN_US: u16 = 988;
+pub const MID_US: u16 = 1500;
+pub const MAX_US: u16 = 2012;
+
+/// Channel pulse widths in microseconds, `MID_US` at centre.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RcChannels {
+    pub us: [u16; MAX_CHANNELS],
+    pub count: u8,
+}
+
+impl Default for RcChannels {
+    fn default() -> Self { Self { us: [MID_US; MAX_CHANNELS], count: 0 } }
+}
+
+impl RcChannels {
+    pub fn get(&self, idx: usize) -> Option<u16> {
+        if idx < self.count as usize { Some(self.us[idx]) } else { None }
+    }
+
+    pub fn as_slice(&self) -> &[u16] { &self.us[..self.count as usize] }
+}
+
+/// 11 bit CRSF and SBUS values, 172..=1811 maps onto 988..=2011 us.
+pub fn ticks_to_us(ticks: u16) -> u16 { ((ticks as i32 - 992) * 5 / 8 + MID_US as i32) as u16 }
+
+pub fn us_to_ticks(us: u16) -> u16 { ((us as i32 - MID_US as i32) * 8 / 5 + 992).clamp(0, 2047) as u16 }
+
+/// Sixteen 11 bit values packed LSB first, as used by both CRSF and SBUS.
+pub fn unpack_11bit(data: &[u8; 22]) -> [u16; 16] {
+    let mut ret = [0; 16];
+    let mut acc = 0u32;
+    let mut bits = 0;
+    let mut idx = 0;
+    for &b in data {
+        acc |= (b as u32) << bits;
+        bits += 8;
+        if bits >= 11 {
+            ret[idx] = (acc & 0x7FF) as u16;
+            acc >>= 11;
+            bits -= 11;
+            idx += 1;
+        }
+    }
+    ret
+}
+
+pub fn pack_11bit(values: &[u16; 16]) -> [u8; 22] {
+    let mut ret = [0; 22];
+    let mut acc = 0u32;
+    let mut bits = 0;
+    let mut idx = 0;
+    for &v in values {
+        acc |= ((v & 0x7FF) as u32) << bits;
+        bits += 11;
+        while bits >= 8 {
+            ret[idx] = acc as u8;
+            acc >>= 8;
+            bits -= 8;
+            idx += 1;
+        }
+    }
+    ret
+}
+
+pub const STICKS: usize = 4;
+pub const AUX_CHANNELS: usize = MAX_CHANNELS - STICKS;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelMap {
+    Aetr,
+    Taer,
+}
+
+impl ChannelMap {
+    /// Raw channel indices of roll, pitch, yaw and throttle.
+    pub fn indices(&self) -> [usize; STICKS] {
+        match self {
+            Self::Aetr => [0, 1, 3, 2],
+            Self::Taer => [1, 2, 3, 0],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Endpoints {
+    pub min_us: u16,
+    pub mid_us: u16,
+    pub max_us: u16,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self { Self { min_us: MIN_US, mid_us: MID_US, max_us: MAX_US } }
+}
+
+impl Endpoints {
+    /// -1.0..=1.0 around `mid_us`, `deadband_us` either side of it reads as zero.
+    pub fn bipolar(&self, us: u16, deadband_us: u16) -> f32 {
+        let offset = us as f32 - self.mid_us as f32;
+        let deadband = deadband_us as f32;
+        if abs(offset) <= deadband {
+            return 0.0;
+        }
+        let ret = if offset > 0.0 {
+            (offset - deadband) / (self.max_us as f32 - self.mid_us as f32 - deadband)
+        } else {
+            (offset + deadband) / (self.mid_us as f32 - self.min_us as f32 - deadband)
+        };
+        ret.clamp(-1.0, 1.0)
+    }
+
+    /// 0.0..=1.0 between the endpoints, `deadband_us` above `min_us` reads as zero.
+    pub fn unipolar(&self, us: u16, deadband_us: u16) -> f32 {
+        let low = (self.min_us + deadband_us) as f32;
+        ((us as f32 - low) / (self.max_us as f32 - low)).clamp(0.0, 1.0)
+    }
+}
+
+fn abs(v: f32) -> f32 { if v < 0.0 { -v } else { v } }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StickConfig {
+    pub endpoints: Endpoints,
+    pub deadband_us: u16,
+    pub expo: f32, // 0.0 linear, 1.0 cubic
+    pub rate: f32, // output at full deflection
+}
+
+impl Default for StickConfig {
+    fn default() -> Self {
+        Self { endpoints: Endpoints::default(), deadband_us: 5, expo: 0.0, rate: 1.0 }
+    }
+}
+
+impl StickConfig {
+    pub fn apply(&self, us: u16) -> f32 {
+        let x = self.endpoints.bipolar(us, self.deadband_us);
+        (x * (1.0 - self.expo) + x * x * x * self.expo) * self.rate
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RcConfig {
+    pub map: ChannelMap,
+    pub roll: StickConfig,
+    pub pitch: StickConfig,
+    pub yaw: StickConfig,
+    pub throttle: StickConfig, // expo and rate unused, shaping belongs to the throttle curve
+    pub arm_channel: Option<usize>, // raw index, armed above `arm_threshold_us`
+    pub arm_threshold_us: u16,
+}
+
+impl Default for RcConfig {
+    fn default() -> Self {
+        Self {
+            map: ChannelMap::Aetr,
+            roll: StickConfig::default(),
+            pitch: StickConfig::default(),
+            yaw: StickConfig::default(),
+            throttle: StickConfig::default(),
+            arm_channel: Some(4),
+            arm_threshold_us: 1700,
+        }
+    }
+}
+
+/// Normalised sticks and switches, independent of protocol and channel order.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RcFrame {
+    pub roll: f32, // -rate..=rate
+    pub pitch: f32,
+    pub yaw: f32,
+    pub throttle: f32, // 0.0..=1.0
+    pub armed: bool,
+    pub aux: [f32; AUX_CHANNELS], // -1.0..=1.0, raw channels after the sticks
+}
+
+impl RcFrame {
+    pub fn new(channels: &RcChannels, config: &RcConfig) -> Self {
+        let [roll, pitch, yaw, throttle] = config.map.indices().map(|idx| channels.get(idx));
+        let [roll, pitch, yaw] = [roll, pitch, yaw].map(|us| us.unwrap_or(MID_US));
+        let mut aux = [0.0; AUX_CHANNELS];
+        for (idx, value) in aux.iter_mut().enumerate() {
+            if let Some(us) = channels.get(STICKS + idx) {
+                *value = Endpoints::default().bipolar(us, 0);
+            }
+        }
+        let armed = match config.arm_channel {
+            Some(idx) => channels.get(idx).is_some_and(|us| us > config.arm_threshold_us),
+            None => true,
+        };
+        Self {
+            roll: config.roll.apply(roll),
+            pitch: config.pitch.apply(pitch),
+            yaw: config.yaw.apply(yaw),
+            throttle: config.throttle.endpoints.unipolar(throttle.unwrap_or(MIN_US), config.throttle.deadband_us),
+            armed,
+            aux,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FailsafeAction {
+    Land { throttle: f32, duration_ms: u64 }, // level sticks at a fixed throttle, then cut
+    Cut,
+}
+
+#[derive(Debug, Clone)]
+pub struct FailsafeConfig {
+    pub signal_timeout_ms: u64, // frames older than this count as lost
+    pub hold_ms: u64, // stage one, repeat the last frame
+    pub action: FailsafeAction, // stage two, straight to cut if the last frame was disarmed
+}
+
+impl Default for FailsafeConfig {
+    fn default() -> Self {
+        Self { signal_timeout_ms: 100, hold_ms: 1000, action: FailsafeAction::Cut }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FailsafeStage {
+    Ok,
+    Hold,
+    Land,
+    Cut, // also before the first frame, cleared by a disarmed frame
+}
+
+pub struct Failsafe {
+    config: FailsafeConfig,
+    last: RcFrame,
+    stage: FailsafeStage,
+    alarm_since: Option<u64>,
+}
+
+impl Failsafe {
+    pub fn new(config: FailsafeConfig) -> Self {
+        Self { config, last: RcFrame::default(), stage: FailsafeStage::Cut, alarm_since: None }
+    }
+
+    pub fn set_config(&mut self, config: FailsafeConfig) { self.config = config; }
+
+    pub fn stage(&self) -> FailsafeStage { self.stage }
+
+    /// Raised by a condition outside the link, e.g. a critical battery. While armed it skips
+    /// the hold stage and runs the failsafe action from `now`, arming while it is raised cuts.
+    pub fn set_alarm(&mut self, alarm: bool, now: u64) {
+        if !alarm {
+            self.alarm_since = None;
+        } else if self.alarm_since.is_none() {
+            self.alarm_since = Some(now);
+        }
+    }
+
+    /// Takes the latest frame and its arrival time, returns what to fly.
+    /// After landing or cutting, the arm switch has to be off before control returns.
+    pub fn update(&mut self, latest: Option<(RcFrame, u64)>, now: u64) -> RcFrame {
+        let cut = RcFrame { armed: false, ..RcFrame::default() };
+        let lost_ms = match latest {
+            Some((frame, at)) if now.saturating_sub(at) <= self.config.signal_timeout_ms => {
+                let was_armed = self.last.armed;
+                self.last = frame;
+                let Some(since) = self.alarm_since.filter(|_| frame.armed) else {
+                    self.stage = match self.stage {
+                        FailsafeStage::Land | FailsafeStage::Cut if frame.armed => return cut,
+                        _ => FailsafeStage::Ok,
+                    };
+                    return frame;
+                };
+                if !was_armed {
+                    self.stage = FailsafeStage::Cut;
+                }
+                self.config.hold_ms + now.saturating_sub(since)
+            }
+            Some((_, at)) => now.saturating_sub(at) - self.config.signal_timeout_ms,
+            None => {
+                self.stage = FailsafeStage::Cut;
+                return cut;
+            }
+        };
+
+        if lost_ms < self.config.hold_ms && matches!(self.stage, FailsafeStage::Ok | FailsafeStage::Hold) {
+            self.stage = FailsafeStage::Hold;
+            return self.last;
+        }
+        match self.config.action {
+            FailsafeAction::Land { throttle, duration_ms }
+                if lost_ms < self.config.hold_ms + duration_ms
+                    && self.last.armed
+                    && self.stage != FailsafeStage::Cut =>
+            {
+                self.stage = FailsafeStage::Land;
+                RcFrame { throttle, armed: true, ..RcFrame::default() }
+            }
+            _ => {
+                self.stage = FailsafeStage::Cut;
+                cut
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: FailsafeConfig = FailsafeConfig {
+        signal_timeout_ms: 50,
+        hold_ms: 100,
+        action: FailsafeAction::Land { throttle: 0.3, duration_ms: 200 },
+    };
+
+    #[test]
+    fn ticks() {
+        assert_eq!(ticks_to_us(172), MIN_US);
+        assert_eq!(ticks_to_us(992), MID_US);
+        assert_eq!(ticks_to_us(1811), 2011);
+        assert_eq!(us_to_ticks(MID_US), 992);
+        assert_eq!(us_to_ticks(0), 0);
+        assert_eq!(us_to_ticks(u16::MAX), 2047);
+        for ticks in (0..=2047).step_by(8) {
+            let us = ticks_to_us(ticks);
+            assert!(us_to_ticks(us).abs_diff(ticks) <= 1, "{} ticks", ticks);
+        }
+    }
+
+    #[test]
+    fn pack_round_trip() {
+        let mut values = [0u16; 16];
+        for (idx, v) in values.iter_mut().enumerate() {
+            *v = (idx as u16 * 0x2A5 + 0x13) & 0x7FF;
+        }
+        values[0] = 0x7FF;
+        values[15] = 0x400;
+        assert_eq!(unpack_11bit(&pack_11bit(&values)), values);
+        assert_eq!(pack_11bit(&[0x7FF; 16]), [0xFF; 22]);
+        let mut single = [0; 16];
+        single[1] = 0x7FF; // bits 11..22
+        let packed = pack_11bit(&single);
+        assert_eq!(&packed[..3], &[0x00, 0xF8, 0x3F]);
+        assert!(packed[3..].iter().all(|b| *b == 0));
+    }
+
+    fn frame(armed: bool) -> RcFrame { RcFrame { throttle: 0.5, armed, ..RcFrame::default() } }
+
+    #[test]
+    fn stages() {
+        let mut failsafe = Failsafe::new(CONFIG);
+        assert_eq!(failsafe.stage(), FailsafeStage::Cut);
+        failsafe.update(Some((frame(false), 0)), 0);
+        assert_eq!(failsafe.stage(), FailsafeStage::Ok);
+        assert_eq!(failsafe.update(Some((frame(true), 10)), 10), frame(true));
+        assert_eq!(failsafe.update(Some((frame(true), 10)), 50), frame(true));
+        assert_eq!(failsafe.stage(), FailsafeStage::Ok);
+
+        assert_eq!(failsafe.update(Some((frame(true), 10)), 100), frame(true));
+        assert_eq!(failsafe.stage(), FailsafeStage::Hold);
+
+        let out = failsafe.update(Some((frame(true), 10)), 200);
+        assert_eq!(failsafe.stage(), FailsafeStage::Land);
+        assert!(out.armed);
+        assert_eq!(out.throttle, 0.3);
+        assert_eq!(out.roll, 0.0);
+
+        let out = failsafe.update(Some((frame(true), 10)), 400);
+        assert_eq!(failsafe.stage(), FailsafeStage::Cut);
+        assert!(!out.armed);
+    }
+
+    #[test]
+    fn disarmed_loss_cuts() {
+        let mut failsafe = Failsafe::new(CONFIG);
+        failsafe.update(Some((frame(false), 0)), 0);
+        let out = failsafe.update(Some((frame(false), 0)), 100);
+        assert_eq!(failsafe.stage(), FailsafeStage::Hold);
+        assert!(!out.armed);
+        let out = failsafe.update(Some((frame(false), 0)), 300);
+        assert_eq!(failsafe.stage(), FailsafeStage::Cut);
+        assert!(!out.armed);
+    }
+
+    #[test]
+    fn rearm_after_disarmed_frame() {
+        let mut failsafe = Failsafe::new(CONFIG);
+        assert!(!failsafe.update(None, 0).armed);
+        assert!(!failsafe.update(Some((frame(true), 10)), 10).armed);
+        assert_eq!(failsafe.stage(), FailsafeStage::Cut);
+
+        failsafe.update(Some((frame(false), 20)), 20);
+        assert_eq!(failsafe.stage(), FailsafeStage::Ok);
+        assert!(failsafe.update(Some((frame(true), 30)), 30).armed);
+
+        failsafe.update(Some((frame(true), 30)), 500);
+        assert_eq!(failsafe.stage(), FailsafeStage::Cut);
+        assert!(!failsafe.update(Some((frame(true), 510)), 510).armed);
+        assert_eq!(failsafe.stage(), FailsafeStage::Cut);
+        failsafe.update(Some((frame(false), 520)), 520);
+        assert!(failsafe.update(Some((frame(true), 530)), 530).armed);
+    }
+
+    #[test]
+    fn alarm_lands_then_cuts() {
+        let mut failsafe = Failsafe::new(CONFIG);
+        failsafe.update(Some((frame(false), 0)), 0);
+        failsafe.update(Some((frame(true), 10)), 10);
+        failsafe.set_alarm(true, 20);
+        let out = failsafe.update(Some((frame(true), 20)), 20);
+        assert_eq!(failsafe.stage(), FailsafeStage::Land);
+        assert_eq!(out.throttle, 0.3);
+        failsafe.set_alarm(true, 100); // keeps the first timestamp
+        failsafe.update(Some((frame(true), 210)), 210);
+        assert_eq!(failsafe.stage(), FailsafeStage::Land);
+        assert!(!failsafe.update(Some((frame(true), 220)), 220).armed);
+        assert_eq!(failsafe.stage(), FailsafeStage::Cut);
+
+        // disarming clears the cut, arming again with the alarm raised does not
+        failsafe.update(Some((frame(false), 230)), 230);
+        assert_eq!(failsafe.stage(), FailsafeStage::Ok);
+        assert!(!failsafe.update(Some((frame(true), 240)), 240).armed);
+        assert_eq!(failsafe.stage(), FailsafeStage::Cut);
+
+        failsafe.set_alarm(false, 250);
+        failsafe.update(Some((frame(false), 250)), 250);
+        assert!(failsafe.update(Some((frame(true), 260)), 260).armed);
+    }
+
+    #[test]
+    fn alarm_with_cut_action() {
+        let mut failsafe = Failsafe::new(FailsafeConfig { action: FailsafeAction::Cut, ..CONFIG });
+        failsafe.update(Some((frame(false), 0)), 0);
+        failsafe.update(Some((frame(true), 10)), 10);
+        failsafe.set_alarm(true, 20);
+        assert!(!failsafe.update(Some((frame(true), 20)), 20).armed);
+        assert_eq!(failsafe.stage(), FailsafeStage::Cut);
+    }
+}
@@ -0,0 +1,183 @@
+//! Futaba SBUS, 25 byte frames of sixteen 11 bit channels, two digital channels and flags.
+
+use crate::rc::{self, RcChannels, MAX_US, MIN_US};
+
+pub const FRAME_LEN: usize = 25;
+const HEADER: u8 = 0x0F;
+const FLAG_CH17: u8 = 0x01;
+const FLAG_CH18: u8 = 0x02;
+const FLAG_FRAME_LOST: u8 = 0x04;
+const FLAG_FAILSAFE: u8 = 0x08;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SbusError {
+    Footer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SbusFrame {
+    pub channels: RcChannels, // 18 channels, the digital ones at the endpoints
+    pub frame_lost: bool, // receiver missed a frame and repeats old values
+    pub failsafe: bool, // receiver lost the link, values are its failsafe outputs
+}
+
+impl SbusFrame {
+    pub fn decode(frame: &[u8; FRAME_LEN]) -> Result<Self, SbusError> {
+        // SBUS2 receivers rotate telemetry slots through the footer
+        let footer = frame[24];
+        if footer != 0x00 && footer & 0x0F != 0x04 {
+            return Err(SbusError::Footer);
+        }
+        let data: &[u8; 22] = frame[1..23].try_into().unwrap();
+        let flags = frame[23];
+        let digital = |flag: u8| if flags & flag != 0 { MAX_US } else { MIN_US };
+
+        let mut channels = RcChannels { count: 18, ..Default::default() };
+        for (us, ticks) in channels.us.iter_mut().zip(rc::unpack_11bit(data)) {
+            *us = rc::ticks_to_us(ticks);
+        }
+        channels.us[16] = digital(FLAG_CH17);
+        channels.us[17] = digital(FLAG_CH18);
+        Ok(Self {
+            channels,
+            frame_lost: flags & FLAG_FRAME_LOST != 0,
+            failsafe: flags & FLAG_FAILSAFE != 0,
+        })
+    }
+
+    pub fn encode(&self) -> [u8; FRAME_LEN] {
+        let mut ticks = [0; 16];
+        for (ticks, us) in ticks.iter_mut().zip(&self.channels.us) {
+            *ticks = rc::us_to_ticks(*us);
+        }
+        let mut ret = [0; FRAME_LEN];
+        ret[0] = HEADER;
+        ret[1..23].copy_from_slice(&rc::pack_11bit(&ticks));
+        let flag = |set: bool, flag: u8| if set { flag } else { 0 };
+        ret[23] = flag(self.channels.us[16] > rc::MID_US, FLAG_CH17)
+            | flag(self.channels.us[17] > rc::MID_US, FLAG_CH18)
+            | flag(self.frame_lost, FLAG_FRAME_LOST)
+            | flag(self.failsafe, FLAG_FAILSAFE);
+        ret
+    }
+}
+
+/// Byte at a time frame parser, call `reset` on inter-frame gaps to resynchronise.
+pub struct Parser {
+    buf: [u8; FRAME_LEN],
+    len: usize,
+}
+
+impl Default for Parser {
+    fn default() -> Self { Self::new() }
+}
+
+impl Parser {
+    pub const fn new() -> Self { Self { buf: [0; FRAME_LEN], len: 0 } }
+
+    pub fn reset(&mut self) { self.len = 0; }
+
+    pub fn push(&mut self, b: u8) -> Option<Result<SbusFrame, SbusError>> {
+        if self.len == 0 && b != HEADER {
+            return None;
+        }
+        self.buf[self.len] = b;
+        self.len += 1;
+        if self.len < FRAME_LEN {
+            return None;
+        }
+        self.len = 0;
+        Some(SbusFrame::decode(&self.buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// All sixteen proportional channels at 992, digital channels and flags clear.
+    const CENTERED: [u8; FRAME_LEN] = [
+        0x0F, 0xE0, 0x03, 0x1F, 0xF8, 0xC0, 0x07, 0x3E, 0xF0, 0x81, 0x0F, 0x7C, 0xE0, 0x03, 0x1F, 0xF8, 0xC0,
+        0x07, 0x3E, 0xF0, 0x81, 0x0F, 0x7C, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn decode_known_frame() {
+        let frame = SbusFrame::decode(&CENTERED).unwrap();
+        assert_eq!(&frame.channels.as_slice()[..16], &[rc::MID_US; 16]);
+        assert_eq!(&frame.channels.as_slice()[16..], &[MIN_US, MIN_US]);
+        assert!(!frame.frame_lost && !frame.failsafe);
+    }
+
+    #[test]
+    fn flags() {
+        let mut raw = CENTERED;
+        raw[23] = FLAG_CH17 | FLAG_FRAME_LOST;
+        let frame = SbusFrame::decode(&raw).unwrap();
+        assert_eq!(frame.channels.get(16), Some(MAX_US));
+        assert_eq!(frame.channels.get(17), Some(MIN_US));
+        assert!(frame.frame_lost && !frame.failsafe);
+
+        raw[23] = FLAG_CH18 | FLAG_FAILSAFE;
+        let frame = SbusFrame::decode(&raw).unwrap();
+        assert_eq!(frame.channels.get(16), Some(MIN_US));
+        assert_eq!(frame.channels.get(17), Some(MAX_US));
+        assert!(!frame.frame_lost && frame.failsafe);
+        assert_eq!(frame.encode()[23], raw[23]);
+    }
+
+    #[test]
+    fn footer() {
+        let mut raw = CENTERED;
+        for footer in [0x00, 0x04, 0x14, 0x24, 0x34] {
+            raw[24] = footer;
+            assert!(SbusFrame::decode(&raw).is_ok(), "footer {:#x}", footer);
+        }
+        for footer in [0x01, 0x05, 0x0F, 0xF0, 0xFF] {
+            raw[24] = footer;
+            assert_eq!(SbusFrame::decode(&raw), Err(SbusError::Footer), "footer {:#x}", footer);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut frame = SbusFrame { channels: RcChannels { count: 18, ..Default::default() }, ..Default::default() };
+        // multiples of 8 ticks from centre convert to whole microseconds and back
+        for (idx, us) in frame.channels.us[..16].iter_mut().enumerate() {
+            *us = rc::ticks_to_us(176 + 104 * idx as u16);
+        }
+        frame.channels.us[16] = MAX_US;
+        frame.channels.us[17] = MIN_US;
+        frame.failsafe = true;
+        let raw = frame.encode();
+        assert_eq!(raw[0], HEADER);
+        assert_eq!(raw[24], 0x00);
+        assert_eq!(SbusFrame::decode(&raw), Ok(frame));
+        assert_eq!(SbusFrame::decode(&CENTERED).unwrap().encode(), CENTERED);
+    }
+
+    #[test]
+    fn parser_resyncs() {
+        let mut parser = Parser::new();
+        for b in [0x00, 0x55, 0xFF] {
+            assert_eq!(parser.push(b), None);
+        }
+        for &b in &CENTERED[..10] {
+            assert_eq!(parser.push(b), None);
+        }
+        parser.reset(); // inter-frame gap in the middle of a frame
+        let mut out = None;
+        for &b in &CENTERED {
+            out = parser.push(b).or(out);
+        }
+        assert!(matches!(out, Some(Ok(_))));
+
+        let mut raw = CENTERED;
+        raw[24] = 0xAA;
+        let mut out = None;
+        for &b in &raw {
+            out = parser.push(b).or(out);
+        }
+        assert_eq!(out, Some(Err(SbusError::Footer)));
+    }
+}
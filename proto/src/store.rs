@@ -0,0 +1,392 @@
+//! Versioned settings in two flash sectors.
+//!
+//! Records are appended to the active sector until it fills up, then the other sector is
+//! erased and takes over, so each save costs one page write and erases are spread out.
+//! The newest record with a valid CRC wins; anything unreadable falls back to defaults.
+
+use bincode::{Decode, Encode};
+
+use crate::crc::crc16_update;
+
+pub const SECTOR_SIZE: usize = 4096;
+pub const SLOT_SIZE: usize = 256; // one flash page
+const SLOTS: usize = SECTOR_SIZE / SLOT_SIZE;
+const HEADER_LEN: usize = 16;
+pub const MAX_PAYLOAD: usize = SLOT_SIZE - HEADER_LEN;
+const MAGIC: u32 = 0x4347_4E50; // "PNGC"
+const ERASED: u32 = 0xFFFF_FFFF;
+
+/// NOR flash, erased bytes read as 0xFF and writes only clear bits.
+pub trait Flash {
+    type Error;
+
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), Self::Error>;
+    /// `offset` and `data` are page aligned.
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Self::Error>;
+    /// Erases the sector starting at `offset`.
+    fn erase(&mut self, offset: u32) -> Result<(), Self::Error>;
+}
+
+/// A settings struct that knows its schema version and how to read older ones.
+pub trait Schema: Encode + Decode + Default {
+    const VERSION: u16;
+
+    /// Converts a payload written by an older schema, `None` falls back to defaults.
+    fn migrate(version: u16, payload: &[u8]) -> Option<Self> {
+        let _ = (version, payload);
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StoreError<E> {
+    Flash(E),
+    Encode, // payload larger than `MAX_PAYLOAD`
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Loaded {
+    Current,
+    Migrated(u16), // from this version
+    Defaults, // nothing valid stored, or migration failed
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Header {
+    seq: u32,
+    version: u16,
+    len: u16,
+    crc: u16,
+}
+
+impl Header {
+    fn to_bytes(self) -> [u8; HEADER_LEN] {
+        let mut ret = [0xFF; HEADER_LEN];
+        ret[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+        ret[4..8].copy_from_slice(&self.seq.to_le_bytes());
+        ret[8..10].copy_from_slice(&self.version.to_le_bytes());
+        ret[10..12].copy_from_slice(&self.len.to_le_bytes());
+        ret[12..14].copy_from_slice(&self.crc.to_le_bytes());
+        ret
+    }
+
+    fn from_bytes(b: &[u8]) -> Option<Self> {
+        if u32::from_le_bytes([b[0], b[1], b[2], b[3]]) != MAGIC {
+            return None;
+        }
+        let ret = Self {
+            seq: u32::from_le_bytes([b[4], b[5], b[6], b[7]]),
+            version: u16::from_le_bytes([b[8], b[9]]),
+            len: u16::from_le_bytes([b[10], b[11]]),
+            crc: u16::from_le_bytes([b[12], b[13]]),
+        };
+        (ret.len as usize <= MAX_PAYLOAD).then_some(ret)
+    }
+
+    fn checksum(seq: u32, version: u16, payload: &[u8]) -> u16 {
+        let crc = crc16_update(0xFFFF, &seq.to_le_bytes());
+        let crc = crc16_update(crc, &version.to_le_bytes());
+        crc16_update(crc, payload)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Slot {
+    sector: usize,
+    index: usize,
+}
+
+/// Settings store over two sectors starting at `base`.
+pub struct Store<F: Flash> {
+    flash: F,
+    base: u32,
+    latest: Option<(Slot, u32)>, // slot and sequence number
+    next: Option<Slot>, // free slot after `latest`, `None` when the sector is full
+}
+
+impl<F: Flash> Store<F> {
+    pub fn new(flash: F, base: u32) -> Self {
+        Self { flash, base, latest: None, next: None }
+    }
+
+    pub fn into_inner(self) -> F { self.flash }
+
+    fn offset(&self, slot: Slot) -> u32 {
+        self.base + (slot.sector * SECTOR_SIZE + slot.index * SLOT_SIZE) as u32
+    }
+
+    /// Reads a slot, `Ok(None)` for free or corrupt slots.
+    fn read_slot(&mut self, slot: Slot, buf: &mut [u8; SLOT_SIZE]) -> Result<Option<Header>, F::Error> {
+        let offset = self.offset(slot);
+        self.flash.read(offset, buf)?;
+        let Some(header) = Header::from_bytes(&buf[..HEADER_LEN]) else {
+            return Ok(None);
+        };
+        let payload = &buf[HEADER_LEN..HEADER_LEN + header.len as usize];
+        Ok((Header::checksum(header.seq, header.version, payload) == header.crc).then_some(header))
+    }
+
+    fn is_free(&mut self, slot: Slot) -> Result<bool, F::Error> {
+        let mut b = [0u8; 4];
+        let offset = self.offset(slot);
+        self.flash.read(offset, &mut b)?;
+        Ok(u32::from_le_bytes(b) == ERASED)
+    }
+
+    /// Finds the newest valid record, returns it in `buf`.
+    fn scan(&mut self, buf: &mut [u8; SLOT_SIZE]) -> Result<Option<Header>, F::Error> {
+        self.latest = None;
+        let mut tmp = [0u8; SLOT_SIZE];
+        let mut ret = None;
+        for sector in 0..2 {
+            for index in 0..SLOTS {
+                let slot = Slot { sector, index };
+                let Some(header) = self.read_slot(slot, &mut tmp)? else {
+                    continue;
+                };
+                if self.latest.is_none_or(|(_, seq)| header.seq > seq) {
+                    self.latest = Some((slot, header.seq));
+                    buf.copy_from_slice(&tmp);
+                    ret = Some(header);
+                }
+            }
+        }
+
+        // append after the newest record, slots behind a torn write are skipped
+        self.next = None;
+        let sector = self.latest.map_or(0, |(slot, _)| slot.sector);
+        let start = self.latest.map_or(0, |(slot, _)| slot.index + 1);
+        for index in start..SLOTS {
+            let slot = Slot { sector, index };
+            if self.is_free(slot)? {
+                self.next = Some(slot);
+                break;
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Loads the newest settings, migrating older schemas and falling back to defaults.
+    pub fn load<T: Schema>(&mut self) -> Result<(T, Loaded), F::Error> {
+        let mut buf = [0u8; SLOT_SIZE];
+        let Some(header) = self.scan(&mut buf)? else {
+            return Ok((T::default(), Loaded::Defaults));
+        };
+        let payload = &buf[HEADER_LEN..HEADER_LEN + header.len as usize];
+        let ret = if header.version == T::VERSION {
+            bincode::decode_from_slice(payload, bincode::config::standard())
+                .ok()
+                .map(|(ret, _)| (ret, Loaded::Current))
+        } else {
+            T::migrate(header.version, payload).map(|ret| (ret, Loaded::Migrated(header.version)))
+        };
+        Ok(ret.unwrap_or_else(|| (T::default(), Loaded::Defaults)))
+    }
+
+    /// Appends a record, erasing the other sector once the active one is full.
+    pub fn save<T: Schema>(&mut self, settings: &T) -> Result<(), StoreError<F::Error>> {
+        let mut buf = [0xFFu8; SLOT_SIZE];
+        let len = bincode::encode_into_slice(settings, &mut buf[HEADER_LEN..], bincode::config::standard())
+            .map_err(|_| StoreError::Encode)?;
+
+        if self.latest.is_none() && self.next.is_none() {
+            let mut tmp = [0u8; SLOT_SIZE];
+            self.scan(&mut tmp).map_err(StoreError::Flash)?;
+        }
+        let seq = self.latest.map_or(0, |(_, seq)| seq.wrapping_add(1));
+        let slot = match self.next {
+            Some(slot) => slot,
+            None => {
+                let sector = self.latest.map_or(0, |(slot, _)| 1 - slot.sector);
+                let offset = self.base + (sector * SECTOR_SIZE) as u32;
+                self.flash.erase(offset).map_err(StoreError::Flash)?;
+                Slot { sector, index: 0 }
+            }
+        };
+
+        let crc = Header::checksum(seq, T::VERSION, &buf[HEADER_LEN..HEADER_LEN + len]);
+        let header = Header { seq, version: T::VERSION, len: len as u16, crc };
+        buf[..HEADER_LEN].copy_from_slice(&header.to_bytes());
+        let offset = self.offset(slot);
+        self.flash.write(offset, &buf).map_err(StoreError::Flash)?;
+
+        self.latest = Some((slot, seq));
+        self.next = (slot.index + 1 < SLOTS).then_some(Slot { sector: slot.sector, index: slot.index + 1 });
+        Ok(())
+    }
+
+    /// Erases both sectors, the next load returns defaults.
+    pub fn reset(&mut self) -> Result<(), F::Error> {
+        self.flash.erase(self.base)?;
+        self.flash.erase(self.base + SECTOR_SIZE as u32)?;
+        self.latest = None;
+        self.next = Some(Slot { sector: 0, index: 0 });
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RamFlashError {
+    OutOfBounds,
+    Unaligned,
+}
+
+/// RAM backed flash for host checks, with NOR semantics and operation counters.
+pub struct RamFlash<const N: usize> {
+    pub data: [u8; N],
+    pub erases: u32,
+    pub writes: u32,
+}
+
+impl<const N: usize> Default for RamFlash<N> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<const N: usize> RamFlash<N> {
+    pub const fn new() -> Self { Self { data: [0xFF; N], erases: 0, writes: 0 } }
+
+    fn range(&self, offset: u32, len: usize) -> Result<core::ops::Range<usize>, RamFlashError> {
+        let start = offset as usize;
+        let end = start.checked_add(len).filter(|end| *end <= N).ok_or(RamFlashError::OutOfBounds)?;
+        Ok(start..end)
+    }
+}
+
+impl<const N: usize> Flash for RamFlash<N> {
+    type Error = RamFlashError;
+
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), RamFlashError> {
+        let range = self.range(offset, buf.len())?;
+        buf.copy_from_slice(&self.data[range]);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), RamFlashError> {
+        if !(offset as usize).is_multiple_of(SLOT_SIZE) || !data.len().is_multiple_of(SLOT_SIZE) {
+            return Err(RamFlashError::Unaligned);
+        }
+        let range = self.range(offset, data.len())?;
+        for (cell, b) in self.data[range].iter_mut().zip(data) {
+            *cell &= *b;
+        }
+        self.writes += 1;
+        Ok(())
+    }
+
+    fn erase(&mut self, offset: u32) -> Result<(), RamFlashError> {
+        if !(offset as usize).is_multiple_of(SECTOR_SIZE) {
+            return Err(RamFlashError::Unaligned);
+        }
+        let range = self.range(offset, SECTOR_SIZE)?;
+        self.data[range].fill(0xFF);
+        self.erases += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Ram = RamFlash<{ 2 * SECTOR_SIZE }>;
+
+    #[derive(Debug, Default, PartialEq, Encode, Decode)]
+    struct V0 {
+        a: u32,
+    }
+
+    impl Schema for V0 {
+        const VERSION: u16 = 0;
+    }
+
+    #[derive(Debug, PartialEq, Encode, Decode)]
+    struct V1 {
+        a: u32,
+        b: u16,
+    }
+
+    impl Default for V1 {
+        fn default() -> Self { Self { a: 1, b: 2 } }
+    }
+
+    impl Schema for V1 {
+        const VERSION: u16 = 1;
+
+        fn migrate(version: u16, payload: &[u8]) -> Option<Self> {
+            match version {
+                0 => {
+                    let (old, _): (V0, _) = bincode::decode_from_slice(payload, bincode::config::standard()).ok()?;
+                    Some(Self { a: old.a, b: 7 })
+                }
+                _ => None,
+            }
+        }
+    }
+
+    fn reopen(store: Store<Ram>) -> Store<Ram> { Store::new(store.into_inner(), 0) }
+
+    #[test]
+    fn empty() {
+        let mut store = Store::new(Ram::new(), 0);
+        assert_eq!(store.load::<V1>().unwrap(), (V1::default(), Loaded::Defaults));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut store = Store::new(Ram::new(), 0);
+        store.save(&V1 { a: 10, b: 20 }).unwrap();
+        store.save(&V1 { a: 11, b: 21 }).unwrap();
+        let mut store = reopen(store);
+        assert_eq!(store.load::<V1>().unwrap(), (V1 { a: 11, b: 21 }, Loaded::Current));
+    }
+
+    #[test]
+    fn rollover() {
+        let mut store = Store::new(Ram::new(), 0);
+        for a in 0..SLOTS as u32 {
+            store.save(&V1 { a, b: 0 }).unwrap();
+        }
+        assert_eq!(store.flash.erases, 0);
+        store.save(&V1 { a: 100, b: 0 }).unwrap();
+        assert_eq!(store.flash.erases, 1);
+
+        let mut store = reopen(store);
+        assert_eq!(store.load::<V1>().unwrap(), (V1 { a: 100, b: 0 }, Loaded::Current));
+        for a in 0..SLOTS as u32 {
+            store.save(&V1 { a: 200 + a, b: 0 }).unwrap();
+        }
+        assert_eq!(store.flash.erases, 2);
+        assert_eq!(store.flash.writes, 2 * SLOTS as u32 + 1);
+
+        let mut store = reopen(store);
+        let last = 200 + SLOTS as u32 - 1;
+        assert_eq!(store.load::<V1>().unwrap(), (V1 { a: last, b: 0 }, Loaded::Current));
+    }
+
+    #[test]
+    fn torn_write() {
+        let mut store = Store::new(Ram::new(), 0);
+        store.save(&V1 { a: 1, b: 1 }).unwrap();
+        store.save(&V1 { a: 2, b: 2 }).unwrap();
+        let mut flash = store.into_inner();
+        flash.data[SLOT_SIZE + HEADER_LEN] ^= 0x01;
+
+        let mut store = Store::new(flash, 0);
+        assert_eq!(store.load::<V1>().unwrap(), (V1 { a: 1, b: 1 }, Loaded::Current));
+        store.save(&V1 { a: 3, b: 3 }).unwrap();
+        let mut store = reopen(store);
+        assert_eq!(store.load::<V1>().unwrap(), (V1 { a: 3, b: 3 }, Loaded::Current));
+    }
+
+    #[test]
+    fn migrate() {
+        let mut store = Store::new(Ram::new(), 0);
+        store.save(&V0 { a: 5 }).unwrap();
+        let mut store = reopen(store);
+        assert_eq!(store.load::<V1>().unwrap(), (V1 { a: 5, b: 7 }, Loaded::Migrated(0)));
+
+        store.save(&V1 { a: 6, b: 8 }).unwrap();
+        let mut store = reopen(store);
+        assert_eq!(store.load::<V0>().unwrap(), (V0::default(), Loaded::Defaults));
+    }
+}
@@ -0,0 +1,257 @@
+//! Binary telemetry: `[version, bincode message, crc16 le]`, COBS encoded and zero delimited.
+
+use core::fmt::{self, Write};
+
+use bincode::{Decode, Encode};
+
+use crate::cobs::{self, CobsError};
+use crate::crc::crc16;
+
+pub const VERSION: u8 = 1;
+pub const MOTORS: usize = 4;
+/// Largest encoded message, including version and checksum.
+pub const MAX_PAYLOAD: usize = 64;
+/// Largest frame on the wire, including the delimiter.
+pub const MAX_FRAME: usize = cobs::max_encoded_len(MAX_PAYLOAD) + 1;
+
+/// Rows only carry the columns of their kind: attitude `roll,pitch,yaw`, motors and rpm one
+/// per motor, battery `voltage_mv,current_ca,consumed_mah,alarm`, status `armed,temperature_cc`.
+pub const HEADER: &str = "time_ms,kind,a,b,c,d";
+
+#[derive(Debug, Clone, Copy, PartialEq, Encode, Decode)]
+pub enum Message {
+    Attitude { roll: f32, pitch: f32, yaw: f32 }, // degrees
+    Motors([u16; MOTORS]), // dshot throttle, 0 when stopped
+    Rpm([Option<u32>; MOTORS]), // eRPM, `None` without ESC telemetry
+    Battery { voltage_mv: u16, current_ca: i16, consumed_mah: u16, alarm: u8 },
+    Status { armed: bool, temperature_cc: i16 }, // temperature in 0.01 C
+}
+
+/// A message and the sender uptime.
+#[derive(Debug, Clone, Copy, PartialEq, Encode, Decode)]
+pub struct Packet {
+    pub time_ms: u32,
+    pub message: Message,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PacketError {
+    Overflow,
+    Cobs,
+    Checksum,
+    Version(u8),
+    Decode,
+}
+
+impl From<CobsError> for PacketError {
+    fn from(op_0: CobsError) -> Self {
+        match op_0 {
+            CobsError::Overflow => Self::Overflow,
+            CobsError::Malformed => Self::Cobs,
+        }
+    }
+}
+
+fn config() -> impl bincode::config::Config { bincode::config::standard() }
+
+impl Packet {
+    pub fn new(time_ms: u32, message: Message) -> Self { Self { time_ms, message } }
+
+    /// Writes a complete frame including the delimiter, returns its length.
+    pub fn encode(&self, out: &mut [u8]) -> Result<usize, PacketError> {
+        let mut payload = [0u8; MAX_PAYLOAD];
+        payload[0] = VERSION;
+        let len = 1 + bincode::encode_into_slice(self, &mut payload[1..MAX_PAYLOAD - 2], config())
+            .map_err(|_| PacketError::Overflow)?;
+        let crc = crc16(&payload[..len]);
+        payload[len..len + 2].copy_from_slice(&crc.to_le_bytes());
+
+        let len = cobs::encode(&payload[..len + 2], out)?;
+        *out.get_mut(len).ok_or(PacketError::Overflow)? = 0;
+        Ok(len + 1)
+    }
+
+    /// Decodes a frame without the delimiter, in place.
+    pub fn decode(frame: &mut [u8]) -> Result<Self, PacketError> {
+        let len = cobs::decode_in_place(frame)?;
+        if len < 3 {
+            return Err(PacketError::Cobs);
+        }
+        let (payload, crc) = frame[..len].split_at(len - 2);
+        if crc16(payload) != u16::from_le_bytes([crc[0], crc[1]]) {
+            return Err(PacketError::Checksum);
+        }
+        if payload[0] != VERSION {
+            return Err(PacketError::Version(payload[0]));
+        }
+        let (ret, read) = bincode::decode_from_slice(&payload[1..], config()).map_err(|_| PacketError::Decode)?;
+        if read != payload.len() - 1 {
+            return Err(PacketError::Decode);
+        }
+        Ok(ret)
+    }
+
+    /// Writes the columns of the message kind, see `HEADER`.
+    pub fn write_csv<W: Write>(&self, w: &mut W) -> fmt::Result {
+        write!(w, "{},", self.time_ms)?;
+        match self.message {
+            Message::Attitude { roll, pitch, yaw } => write!(w, "attitude,{:.2},{:.2},{:.2}", roll, pitch, yaw),
+            Message::Motors(m) => write!(w, "motors,{},{},{},{}", m[0], m[1], m[2], m[3]),
+            Message::Rpm(rpm) => {
+                w.write_str("rpm")?;
+                for erpm in rpm {
+                    match erpm {
+                        Some(erpm) => write!(w, ",{}", erpm)?,
+                        None => w.write_char(',')?,
+                    }
+                }
+                Ok(())
+            }
+            Message::Battery { voltage_mv, current_ca, consumed_mah, alarm } => {
+                write!(w, "battery,{},{},{},{}", voltage_mv, current_ca, consumed_mah, alarm)
+            }
+            Message::Status { armed, temperature_cc } => write!(w, "status,{},{}", armed as u8, temperature_cc),
+        }
+    }
+}
+
+/// Collects bytes from a stream into frames, resynchronising on the delimiter.
+pub struct Decoder {
+    buf: [u8; MAX_FRAME],
+    len: usize,
+    overflow: bool,
+}
+
+impl Default for Decoder {
+    fn default() -> Self { Self::new() }
+}
+
+impl Decoder {
+    pub const fn new() -> Self { Self { buf: [0; MAX_FRAME], len: 0, overflow: false } }
+
+    pub fn push(&mut self, byte: u8) -> Option<Result<Packet, PacketError>> {
+        if byte != 0 {
+            match self.buf.get_mut(self.len) {
+                Some(b) => {
+                    *b = byte;
+                    self.len += 1;
+                }
+                None => self.overflow = true,
+            }
+            return None;
+        }
+
+        let (len, overflow) = (self.len, self.overflow);
+        self.len = 0;
+        self.overflow = false;
+        match (len, overflow) {
+            (0, false) => None, // back to back delimiters
+            (_, true) => Some(Err(PacketError::Overflow)),
+            _ => Some(Packet::decode(&mut self.buf[..len])),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::string::String;
+    use std::vec::Vec;
+
+    use super::*;
+
+    const MESSAGES: [Message; 6] = [
+        Message::Attitude { roll: 1.5, pitch: -2.25, yaw: 180.0 },
+        Message::Motors([240, 0, 0, 1999]),
+        Message::Rpm([Some(12_000), None, Some(0), None]),
+        Message::Battery { voltage_mv: 12_600, current_ca: -150, consumed_mah: 420, alarm: 1 },
+        Message::Status { armed: true, temperature_cc: 3_150 },
+        Message::Status { armed: false, temperature_cc: -500 },
+    ];
+
+    fn frame(time_ms: u32, message: Message) -> ([u8; MAX_FRAME], usize) {
+        let mut ret = [0u8; MAX_FRAME];
+        let len = Packet::new(time_ms, message).encode(&mut ret).unwrap();
+        (ret, len)
+    }
+
+    #[test]
+    fn round_trip() {
+        for (idx, message) in MESSAGES.into_iter().enumerate() {
+            let (mut buf, len) = frame(idx as u32 * 1000, message);
+            assert_eq!(buf[len - 1], 0);
+            assert!(!buf[..len - 1].contains(&0));
+            assert_eq!(Packet::decode(&mut buf[..len - 1]), Ok(Packet::new(idx as u32 * 1000, message)));
+        }
+    }
+
+    #[test]
+    fn rejects_corruption() {
+        let (mut buf, len) = frame(7, MESSAGES[3]);
+        buf[1] ^= 0x02; // version byte, non-zero either way
+        assert_eq!(Packet::decode(&mut buf[..len - 1]), Err(PacketError::Checksum));
+
+        let (mut buf, len) = frame(7, MESSAGES[3]);
+        buf[0] = 0xFE; // code byte running past the end
+        assert_eq!(Packet::decode(&mut buf[..len - 1]), Err(PacketError::Cobs));
+
+        assert_eq!(Packet::decode(&mut [0x02, 0x01]), Err(PacketError::Cobs));
+    }
+
+    #[test]
+    fn rejects_version() {
+        let mut payload = [0u8; MAX_PAYLOAD];
+        payload[0] = VERSION + 1;
+        let len = 1 + bincode::encode_into_slice(Packet::new(1, MESSAGES[0]), &mut payload[1..], config()).unwrap();
+        let crc = crc16(&payload[..len]);
+        payload[len..len + 2].copy_from_slice(&crc.to_le_bytes());
+        let mut buf = [0u8; MAX_FRAME];
+        let len = cobs::encode(&payload[..len + 2], &mut buf).unwrap();
+        assert_eq!(Packet::decode(&mut buf[..len]), Err(PacketError::Version(VERSION + 1)));
+    }
+
+    #[test]
+    fn decoder_resyncs() {
+        let mut stream = std::vec![0x55, 0x13, 0x00, 0x00];
+        for (idx, message) in MESSAGES.into_iter().enumerate() {
+            let (buf, len) = frame(idx as u32, message);
+            stream.extend_from_slice(&buf[..len]);
+        }
+        let (mut buf, len) = frame(99, MESSAGES[1]);
+        buf[1] ^= 0x02;
+        stream.extend_from_slice(&buf[..len]);
+        stream.extend(core::iter::repeat_n(0xAA, MAX_FRAME + 4)); // runaway frame
+        stream.push(0);
+        let (buf, len) = frame(100, MESSAGES[4]);
+        stream.extend_from_slice(&buf[..len]);
+
+        let mut decoder = Decoder::new();
+        let out: Vec<_> = stream.iter().filter_map(|&b| decoder.push(b)).collect();
+        assert!(out[0].is_err()); // garbage before the first delimiter
+        for (idx, message) in MESSAGES.into_iter().enumerate() {
+            assert_eq!(out[1 + idx], Ok(Packet::new(idx as u32, message)));
+        }
+        assert_eq!(out[7], Err(PacketError::Checksum));
+        assert_eq!(out[8], Err(PacketError::Overflow));
+        assert_eq!(out[9], Ok(Packet::new(100, MESSAGES[4])));
+        assert_eq!(out.len(), 10);
+    }
+
+    #[test]
+    fn csv_columns() {
+        let rows = [
+            "0,attitude,1.50,-2.25,180.00",
+            "1,motors,240,0,0,1999",
+            "2,rpm,12000,,0,",
+            "3,battery,12600,-150,420,1",
+            "4,status,1,3150",
+            "5,status,0,-500",
+        ];
+        for (idx, (message, row)) in MESSAGES.into_iter().zip(rows).enumerate() {
+            let mut csv = String::new();
+            Packet::new(idx as u32, message).write_csv(&mut csv).unwrap();
+            assert_eq!(csv, row);
+        }
+    }
+}
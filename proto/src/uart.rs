@@ -0,0 +1,153 @@
+//! Bit level framing for the PIO UART, kept free of hardware so it can be checked on a host.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameError {
+    Framing, // stop bit sampled low
+    Parity,
+    Break, // line held low for the whole frame
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Framing {
+    pub data_bits: u8, // 5..=9
+    pub parity: Parity,
+    pub stop_bits: u8, // 1 or 2
+}
+
+impl Default for Framing {
+    fn default() -> Self { Self::N81 }
+}
+
+impl Framing {
+    pub const N81: Self = Self { data_bits: 8, parity: Parity::None, stop_bits: 1 };
+    pub const E82: Self = Self { data_bits: 8, parity: Parity::Even, stop_bits: 2 };
+
+    pub fn is_valid(&self) -> bool {
+        (5..=9).contains(&self.data_bits) && (1..=2).contains(&self.stop_bits)
+    }
+
+    fn parity_bits(&self) -> u32 {
+        match self.parity {
+            Parity::None => 0,
+            Parity::Even | Parity::Odd => 1,
+        }
+    }
+
+    fn parity_of(&self, data: u16) -> u32 {
+        let ones = data.count_ones();
+        match self.parity {
+            Parity::None => 0,
+            Parity::Even => ones & 1,
+            Parity::Odd => !ones & 1,
+        }
+    }
+
+    fn data_mask(&self) -> u16 { ((1u32 << self.data_bits) - 1) as u16 }
+
+    /// Bits after the start bit, sent LSB first.
+    pub fn tx_bits(&self) -> u32 { self.data_bits as u32 + self.parity_bits() + self.stop_bits as u32 }
+
+    /// Bits sampled by the receiver after the start bit, up to and including the first stop bit.
+    pub fn rx_bits(&self) -> u32 { self.data_bits as u32 + self.parity_bits() + 1 }
+
+    /// Frame without its start bit, stop bits are ones.
+    pub fn encode(&self, data: u16) -> u32 {
+        let data = data & self.data_mask();
+        let mut ret = data as u32;
+        let mut len = self.data_bits as u32;
+        if self.parity != Parity::None {
+            ret |= self.parity_of(data) << len;
+            len += 1;
+        }
+        ret | (((1 << self.stop_bits) - 1) << len)
+    }
+
+    /// Decodes a word pushed by the receiver. Bits are shifted in from the left,
+    /// so the first data bit ends up at `32 - rx_bits`.
+    pub fn decode(&self, raw: u32) -> Result<u16, FrameError> {
+        let bits = raw >> (32 - self.rx_bits());
+        let data = bits as u16 & self.data_mask();
+        let mut len = self.data_bits as u32;
+        let parity = bits >> len & 1;
+        len += self.parity_bits();
+        let stop = bits >> len & 1;
+
+        if stop == 0 {
+            if bits == 0 {
+                return Err(FrameError::Break);
+            }
+            return Err(FrameError::Framing);
+        }
+        if self.parity != Parity::None && parity != self.parity_of(data) {
+            return Err(FrameError::Parity);
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const O71: Framing = Framing { data_bits: 7, parity: Parity::Odd, stop_bits: 1 };
+    const N92: Framing = Framing { data_bits: 9, parity: Parity::None, stop_bits: 2 };
+    const E51: Framing = Framing { data_bits: 5, parity: Parity::Even, stop_bits: 1 };
+
+    /// What the receiver pushes for a frame, sampled bits left aligned.
+    fn received(framing: &Framing, frame: u32) -> u32 {
+        let bits = framing.rx_bits();
+        (frame & ((1 << bits) - 1)) << (32 - bits)
+    }
+
+    #[test]
+    fn round_trip() {
+        for framing in [Framing::N81, Framing::E82, O71, N92, E51] {
+            assert!(framing.is_valid());
+            for data in 0..(1u16 << framing.data_bits) {
+                let raw = received(&framing, framing.encode(data));
+                assert_eq!(framing.decode(raw), Ok(data), "{:?} {:#x}", framing, data);
+            }
+        }
+    }
+
+    #[test]
+    fn encode_bits() {
+        assert_eq!(Framing::N81.encode(0x55), 0x155);
+        assert_eq!(Framing::E82.encode(0x03), 0x603); // even ones, parity 0
+        assert_eq!(Framing::E82.encode(0x01), 0x701);
+        assert_eq!(O71.encode(0x01), 0x101); // odd ones, parity 0
+        assert_eq!(Framing::E82.tx_bits(), 11);
+        assert_eq!(Framing::E82.rx_bits(), 10);
+    }
+
+    #[test]
+    fn parity_error() {
+        for framing in [Framing::E82, O71, E51] {
+            let frame = framing.encode(0x11) ^ (1 << framing.data_bits);
+            assert_eq!(framing.decode(received(&framing, frame)), Err(FrameError::Parity));
+        }
+    }
+
+    #[test]
+    fn framing_error() {
+        for framing in [Framing::N81, Framing::E82, O71, N92] {
+            let frame = framing.encode(0x2A) & !(1 << (framing.rx_bits() - 1));
+            assert_eq!(framing.decode(received(&framing, frame)), Err(FrameError::Framing));
+        }
+        assert_eq!(Framing::N81.decode(0), Err(FrameError::Break));
+        assert_eq!(Framing::E82.decode(0), Err(FrameError::Break));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(!Framing { data_bits: 4, ..Framing::N81 }.is_valid());
+        assert!(!Framing { stop_bits: 3, ..Framing::N81 }.is_valid());
+    }
+}